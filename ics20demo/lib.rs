@@ -5,6 +5,7 @@
 use ink::env::{chain_extension::FromStatusCode, DefaultEnvironment, Environment};
 use ink::prelude::string::String;
 use ink::prelude::vec::Vec;
+use trait_ibc::ibc::IbcTimeoutBlock;
 
 /// General result type.
 pub type Result<T> = core::result::Result<T, IBCICS20Error>;
@@ -15,6 +16,7 @@ type DefaultBalance = <ink::env::DefaultEnvironment as Environment>::Balance;
 pub trait IBCICS20Extension {
     type ErrorCode = IBCICS20Error;
 
+    /// Superseded by `send_transfer`, which carries the memo and a timeout height.
     #[ink(extension = 0x20001)]
     fn raw_tranfer(
         source_channel: Vec<u8>,
@@ -26,6 +28,78 @@ pub trait IBCICS20Extension {
         timeout_height: u64,
     ) -> Result<()>;
 
+    /// Sends an ICS-20 transfer packet and returns its sequence. The tokens are escrowed or
+    /// burnt from `sender`, the calling contract or its caller, and refunded to it.
+    /// `timeout_timestamp` is in nanoseconds, zero disables it; `None` disables the timeout
+    /// height.
+    #[ink(extension = 0x20007)]
+    fn send_transfer(
+        source_channel: Vec<u8>,
+        denom: Vec<u8>,
+        amount: Vec<u8>,
+        sender: Vec<u8>,
+        receiver: Vec<u8>,
+        timeout_height: Option<IbcTimeoutBlock>,
+        timeout_timestamp: u64,
+        memo: Vec<u8>,
+    ) -> Result<u64>;
+
+    // IBC state queries
+
+    /// Every channel end bound to a port on this chain.
+    #[ink(extension = 0x20002)]
+    fn channels() -> Result<Vec<IbcChannelEnd>>;
+
+    /// The channel end `channel_id` on `port_id`, `None` if it was never opened.
+    #[ink(extension = 0x20003)]
+    fn channel(port_id: Vec<u8>, channel_id: Vec<u8>) -> Result<Option<IbcChannelEnd>>;
+
+    /// The sequence the next packet sent over the channel will get.
+    #[ink(extension = 0x20004)]
+    fn next_sequence_send(port_id: Vec<u8>, channel_id: Vec<u8>) -> Result<u64>;
+
+    /// The trace the transfer module stores for the voucher denom `ibc/{hash}`.
+    #[ink(extension = 0x20005)]
+    fn denom_trace(hash: [u8; 32]) -> Result<Option<IbcDenomTrace>>;
+
+    /// The account the transfer module escrows native tokens sent over the channel in.
+    #[ink(extension = 0x20006)]
+    fn escrow_address(port_id: Vec<u8>, channel_id: Vec<u8>) -> Result<DefaultAccountId>;
+
+    /// Pays native tokens or pallet-assets out of the escrow account of the channel to
+    /// `receiver`, for tokens coming home and refunds of failed sends. `amount` is a decimal
+    /// string.
+    #[ink(extension = 0x20008)]
+    fn unescrow(
+        port_id: Vec<u8>,
+        channel_id: Vec<u8>,
+        denom: Vec<u8>,
+        amount: Vec<u8>,
+        receiver: DefaultAccountId,
+    ) -> Result<()>;
+
+    /// Mints vouchers of the full denom path `denom` to `receiver`, for foreign tokens
+    /// received over a channel and refunds of burnt vouchers. `amount` is a decimal string.
+    #[ink(extension = 0x20009)]
+    fn mint(denom: Vec<u8>, amount: Vec<u8>, receiver: DefaultAccountId) -> Result<()>;
+
+    /// Burns vouchers of the full denom path `denom` held by `owner`, which must be the
+    /// calling contract.
+    #[ink(extension = 0x2000a)]
+    fn burn(denom: Vec<u8>, amount: Vec<u8>, owner: DefaultAccountId) -> Result<()>;
+
+    /// Moves native tokens or pallet-assets of `owner`, which must be the calling contract,
+    /// into the escrow account of the channel, giving back what was paid out of it. `amount`
+    /// is a decimal string.
+    #[ink(extension = 0x2000b)]
+    fn escrow(
+        port_id: Vec<u8>,
+        channel_id: Vec<u8>,
+        denom: Vec<u8>,
+        amount: Vec<u8>,
+        owner: DefaultAccountId,
+    ) -> Result<()>;
+
     // PSP37 interface queries
 
     #[ink(extension = 0x30001)]
@@ -59,11 +133,73 @@ pub trait IBCICS20Extension {
     ) -> Result<()>;
 }
 
-#[derive(scale::Encode, scale::Decode)]
+/// Failures reported by the chain extension. The status codes mirror the errors of
+/// pallet-ics20-transfer (10-19) and pallet-assets (20-29).
+#[derive(scale::Encode, scale::Decode, Clone, Copy, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
 pub enum IBCICS20Error {
+    /// 1: the IBC pallet rejected the call for a reason not listed here
     FailIBCCall,
+    /// 2: the call or its result could not be SCALE decoded
     FailScaleCode,
+    /// 10: no such channel on the transfer port
+    UnknownChannel,
+    /// 11: the channel exists but is not open
+    ChannelNotOpen,
+    /// 12: the denom is malformed or has no trace
+    InvalidDenom,
+    /// 13: zero or unparsable amount
+    InvalidAmount,
+    /// 14: the sender or receiver address is invalid
+    InvalidAddress,
+    /// 15: outgoing transfers are disabled
+    SendDisabled,
+    /// 16: incoming transfers are disabled
+    ReceiveDisabled,
+    /// 17: the timeout is already in the past
+    InvalidTimeout,
+    /// 20: the account doesn't hold enough of the asset
+    InsufficientBalance,
+    /// 21: the spender's allowance is too low
+    InsufficientAllowance,
+    /// 22: no asset with that id
+    UnknownAsset,
+    /// 23: the asset is frozen
+    AssetFrozen,
+    /// 24: the account is frozen for the asset
+    AccountFrozen,
+    /// 25: the transfer would leave an account below the asset's minimum balance
+    BelowMinimum,
+    /// 26: the caller lacks the permission for this asset operation
+    NoPermission,
+    /// any status code this contract doesn't know yet
+    Unknown(u32),
+}
+
+impl IBCICS20Error {
+    /// The status code the chain extension reports this error with.
+    pub fn status_code(&self) -> u32 {
+        match self {
+            Self::FailIBCCall => 1,
+            Self::FailScaleCode => 2,
+            Self::UnknownChannel => 10,
+            Self::ChannelNotOpen => 11,
+            Self::InvalidDenom => 12,
+            Self::InvalidAmount => 13,
+            Self::InvalidAddress => 14,
+            Self::SendDisabled => 15,
+            Self::ReceiveDisabled => 16,
+            Self::InvalidTimeout => 17,
+            Self::InsufficientBalance => 20,
+            Self::InsufficientAllowance => 21,
+            Self::UnknownAsset => 22,
+            Self::AssetFrozen => 23,
+            Self::AccountFrozen => 24,
+            Self::BelowMinimum => 25,
+            Self::NoPermission => 26,
+            Self::Unknown(code) => *code,
+        }
+    }
 }
 
 impl FromStatusCode for IBCICS20Error {
@@ -71,23 +207,107 @@ impl FromStatusCode for IBCICS20Error {
         match status_code {
             0 => Ok(()),
             1 => Err(Self::FailIBCCall),
-            _ => panic!("encountered unknown status code"),
+            2 => Err(Self::FailScaleCode),
+            10 => Err(Self::UnknownChannel),
+            11 => Err(Self::ChannelNotOpen),
+            12 => Err(Self::InvalidDenom),
+            13 => Err(Self::InvalidAmount),
+            14 => Err(Self::InvalidAddress),
+            15 => Err(Self::SendDisabled),
+            16 => Err(Self::ReceiveDisabled),
+            17 => Err(Self::InvalidTimeout),
+            20 => Err(Self::InsufficientBalance),
+            21 => Err(Self::InsufficientAllowance),
+            22 => Err(Self::UnknownAsset),
+            23 => Err(Self::AssetFrozen),
+            24 => Err(Self::AccountFrozen),
+            25 => Err(Self::BelowMinimum),
+            26 => Err(Self::NoPermission),
+            code => Err(Self::Unknown(code)),
         }
     }
 }
 
-// todo(smith) need parse scale error, this is for test
 impl From<scale::Error> for IBCICS20Error {
     fn from(_: scale::Error) -> Self {
-        panic!("encountered unexpected invalid SCALE encoding")
+        Self::FailScaleCode
+    }
+}
+
+/// ICS-04 channel states, as stored by the IBC pallet.
+#[derive(scale::Encode, scale::Decode, Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum IbcChannelState {
+    Uninitialized,
+    Init,
+    TryOpen,
+    Open,
+    Closed,
+}
+
+/// A channel end as seen by the IBC pallet. Identifiers are SCALE encoded like the byte
+/// strings the pallet keeps them as.
+#[derive(scale::Encode, scale::Decode, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub struct IbcChannelEnd {
+    pub port_id: String,
+    pub channel_id: String,
+    pub state: IbcChannelState,
+    pub connection_id: String,
+    pub counterparty_port_id: String,
+    pub counterparty_channel_id: String,
+    pub version: String,
+}
+
+/// A denom trace of the IBC pallet's transfer module.
+#[derive(scale::Encode, scale::Decode, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub struct IbcDenomTrace {
+    pub path: String,
+    pub base_denom: String,
+}
+
+impl core::fmt::Display for IBCICS20Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::FailIBCCall => write!(f, "IBC call failed"),
+            Self::FailScaleCode => write!(f, "SCALE codec failure"),
+            Self::UnknownChannel => write!(f, "Unknown channel"),
+            Self::ChannelNotOpen => write!(f, "Channel is not open"),
+            Self::InvalidDenom => write!(f, "Invalid denom"),
+            Self::InvalidAmount => write!(f, "Invalid amount"),
+            Self::InvalidAddress => write!(f, "Invalid address"),
+            Self::SendDisabled => write!(f, "Sending transfers is disabled"),
+            Self::ReceiveDisabled => write!(f, "Receiving transfers is disabled"),
+            Self::InvalidTimeout => write!(f, "Invalid timeout"),
+            Self::InsufficientBalance => write!(f, "Insufficient balance"),
+            Self::InsufficientAllowance => write!(f, "Insufficient allowance"),
+            Self::UnknownAsset => write!(f, "Unknown asset"),
+            Self::AssetFrozen => write!(f, "Asset is frozen"),
+            Self::AccountFrozen => write!(f, "Account is frozen"),
+            Self::BelowMinimum => write!(f, "Balance would fall below the minimum"),
+            Self::NoPermission => write!(f, "No permission"),
+            Self::Unknown(code) => write!(f, "Unknown status code {}", code),
+        }
     }
 }
 
 impl From<IBCICS20Error> for String {
     fn from(e: IBCICS20Error) -> Self {
+        use ink::prelude::string::ToString;
+        e.to_string()
+    }
+}
+
+impl From<IBCICS20Error> for openbrush::contracts::psp37::PSP37Error {
+    fn from(e: IBCICS20Error) -> Self {
+        use openbrush::contracts::psp37::PSP37Error;
         match e {
-            IBCICS20Error::FailIBCCall => "FailIBCCall".into(),
-            IBCICS20Error::FailScaleCode => "FailScaleCode".into(),
+            IBCICS20Error::InsufficientBalance => PSP37Error::InsufficientBalance,
+            IBCICS20Error::InsufficientAllowance | IBCICS20Error::NoPermission => {
+                PSP37Error::NotAllowed
+            }
+            e => PSP37Error::Custom(e.into()),
         }
     }
 }
@@ -110,6 +330,7 @@ impl Environment for IBCDefaultEnvironment {
 
 #[openbrush::contract(env = crate::IBCDefaultEnvironment)]
 pub mod my_psp37_wrapper {
+    use crate::{IBCICS20Error, IbcChannelEnd, IbcChannelState};
     use trait_ibc::ibc::*;
 
     use ibc::core::ics24_host::identifier::ChannelId;
@@ -118,9 +339,14 @@ pub mod my_psp37_wrapper {
     use ink::prelude::borrow::ToOwned;
     use ink::prelude::{
         string::{String, ToString},
+        vec,
         vec::Vec,
     };
-    use ink::storage::Mapping;
+    use ink::env::call::{build_call, ExecutionInput, Selector};
+    use ink::env::CallFlags;
+    use ink::prelude::format;
+    use ink::storage::{Lazy, Mapping};
+    use openbrush::contracts::traits::psp22::PSP22Error;
     use openbrush::contracts::psp37::*;
     use openbrush::traits::Storage;
 
@@ -133,6 +359,33 @@ pub mod my_psp37_wrapper {
     pub const ICS20_VERSION: &str = "ics20-1";
     pub const ICS20_ORDERING: IbcOrder = IbcOrder::Unordered;
 
+    /// Selectors of the standard PSP22 messages we call on token contracts.
+    const PSP22_TRANSFER: [u8; 4] = ink::selector_bytes!("PSP22::transfer");
+    const PSP22_TRANSFER_FROM: [u8; 4] = ink::selector_bytes!("PSP22::transfer_from");
+    /// Selector of our own `run_hook`.
+    const RUN_HOOK: [u8; 4] = ink::selector_bytes!("run_hook");
+
+    /// Call arguments that are SCALE encoded already, passed on as they are.
+    struct EncodedArgs<'a>(&'a [u8]);
+
+    impl Encode for EncodedArgs<'_> {
+        fn size_hint(&self) -> usize {
+            self.0.len()
+        }
+
+        fn encode_to<T: scale::Output + ?Sized>(&self, dest: &mut T) {
+            dest.write(self.0)
+        }
+    }
+
+    /// Page size of `query_list_allowed` if the caller doesn't set one, and its upper bound.
+    pub const DEFAULT_LIMIT: u32 = 10;
+    pub const MAX_LIMIT: u32 = 30;
+
+    fn handshake_policy() -> HandshakePolicy {
+        HandshakePolicy::new(ICS20_VERSION).with_order(ICS20_ORDERING)
+    }
+
     #[cfg(feature = "std")]
     use ink::storage::traits::StorageLayout;
 
@@ -143,21 +396,14 @@ pub mod my_psp37_wrapper {
         pub funds: Vec<Coin>,
     }
 
-    #[derive(Decode, Encode)]
+    /// An amount of a PSP22 token, identified by the address of its contract.
+    #[derive(Decode, Encode, Clone)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
-    pub struct Cw20Coin {
+    pub struct Psp22Coin {
         pub address: String,
         pub amount: u128,
     }
 
-    #[derive(Decode, Encode)]
-    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
-    pub struct Cw20ReceiveMsg {
-        pub sender: String,
-        pub amount: u128,
-        pub msg: Vec<u8>,
-    }
-
     #[derive(Decode, Encode)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub struct AllowMsg {
@@ -172,7 +418,7 @@ pub mod my_psp37_wrapper {
         pub default_timeout: u64,
         /// who can allow more contracts
         pub gov_contract: String,
-        /// initial allowlist - all cw20 tokens we will send must be previously allowed by governance
+        /// initial allowlist - all PSP22 tokens we will send must be previously allowed by governance
         pub allowlist: Vec<AllowMsg>,
         /// If set, contracts off the allowlist will run with this gas limit.
         /// If unset, will refuse to accept any contract off the allow list.
@@ -190,11 +436,14 @@ pub mod my_psp37_wrapper {
         pub remote_address: String,
         /// How long the packet lives in seconds. If not specified, use default_timeout
         pub timeout: Option<u64>,
+        /// Block height on the counterparty chain after which the packet times out as well
+        pub timeout_height: TimeoutHeight,
         /// An optional memo to add to the IBC transfer
         pub memo: Option<String>,
     }
 
     #[derive(Decode, Encode, Clone, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub enum TimeoutHeight {
         Never,
         At(ibc::core::ics02_client::height::Height),
@@ -220,27 +469,29 @@ pub mod my_psp37_wrapper {
         pub timeout_timestamp: ibc::timestamp::Timestamp,
     }
 
-    #[derive(Decode, Encode)]
+    /// Denoms of PSP22 tokens are their contract address behind this prefix.
+    pub const PSP22_DENOM_PREFIX: &str = "psp22:";
+
+    #[derive(Decode, Encode, Clone)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub enum Amount {
         Native(Coin),
-        // FIXME? USe Cw20CoinVerified, and validate cw20 addresses
-        Cw20(Cw20Coin),
+        Psp22(Psp22Coin),
     }
 
     impl Amount {
-        // TODO: write test for this
         pub fn from_parts(denom: String, amount: u128) -> Self {
-            if denom.starts_with("cw20:") {
-                let address = denom.get(5..).unwrap().into();
-                Amount::Cw20(Cw20Coin { address, amount })
-            } else {
-                Amount::Native(Coin { denom, amount })
+            match denom.strip_prefix(PSP22_DENOM_PREFIX) {
+                Some(address) => Amount::Psp22(Psp22Coin {
+                    address: address.into(),
+                    amount,
+                }),
+                None => Amount::Native(Coin { denom, amount }),
             }
         }
 
-        pub fn cw20(amount: u128, addr: &str) -> Self {
-            Amount::Cw20(Cw20Coin {
+        pub fn psp22(amount: u128, addr: &str) -> Self {
+            Amount::Psp22(Psp22Coin {
                 address: addr.into(),
                 amount: amount,
             })
@@ -258,14 +509,14 @@ pub mod my_psp37_wrapper {
         pub fn denom(&self) -> String {
             match self {
                 Amount::Native(c) => c.denom.clone(),
-                Amount::Cw20(c) => "cw20:".to_owned() + c.address.as_str(),
+                Amount::Psp22(c) => PSP22_DENOM_PREFIX.to_owned() + c.address.as_str(),
             }
         }
 
         pub fn amount(&self) -> u128 {
             match self {
                 Amount::Native(c) => c.amount,
-                Amount::Cw20(c) => c.amount,
+                Amount::Psp22(c) => c.amount,
             }
         }
 
@@ -277,7 +528,7 @@ pub mod my_psp37_wrapper {
         pub fn is_empty(&self) -> bool {
             match self {
                 Amount::Native(c) => c.amount == 0,
-                Amount::Cw20(c) => c.amount == 0,
+                Amount::Psp22(c) => c.amount == 0,
             }
         }
     }
@@ -324,6 +575,23 @@ pub mod my_psp37_wrapper {
         pub total_sent: Vec<Amount>,
     }
 
+    /// The rate limit of a channel and denom, with the flows of the current window.
+    #[derive(Decode, Encode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct RateLimitResponse {
+        pub quota: Quota,
+        /// block time in milliseconds the current window started at, and ends at
+        pub window_start: u64,
+        pub window_end: u64,
+        /// the supply percentage quotas were taken of when the window started
+        pub channel_value: u128,
+        pub inflow: u128,
+        pub outflow: u128,
+        /// how much may still come in and go out in this window, `None` if unlimited
+        pub inflow_left: Option<u128>,
+        pub outflow_left: Option<u128>,
+    }
+
     #[derive(Decode, Encode)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub struct ConfigResponse {
@@ -332,6 +600,12 @@ pub mod my_psp37_wrapper {
         pub gov_contract: String,
     }
 
+    #[derive(Decode, Encode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct AdminResponse {
+        pub admin: String,
+    }
+
     #[derive(Decode, Encode)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub struct AllowedResponse {
@@ -348,14 +622,37 @@ pub mod my_psp37_wrapper {
     #[derive(Decode, Encode)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub enum ExecuteMsg {
-        /// This accepts a properly-encoded ReceiveMsg from a cw20 contract
-        Receive(Cw20ReceiveMsg),
         /// This allows us to transfer *exactly one* native token
         Transfer(TransferMsg),
-        /// This must be called by gov_contract, will allow a new cw20 token to be sent
+        /// This must be called by gov_contract, will allow a new PSP22 token to be sent
         Allow(AllowMsg),
         /// Change the admin (must be called by current admin)
         UpdateAdmin { admin: String },
+        /// This must be called by gov_contract, links a pallet-asset to its IBC base denom
+        RegisterAsset { id: u32, denom: String },
+        /// Send a registered pallet-asset, escrowed from the caller
+        TransferAsset {
+            id: u32,
+            amount: u128,
+            channel: String,
+            receiver: String,
+        },
+        /// This must be called by gov_contract, limits the flows of a denom over a channel
+        SetQuota {
+            channel: String,
+            denom: String,
+            quota: Quota,
+        },
+        /// This must be called by gov_contract, starts a fresh window for a rate limit
+        ResetQuota { channel: String, denom: String },
+        /// This must be called by gov_contract, lifts a rate limit
+        RemoveQuota { channel: String, denom: String },
+        /// Replace the config (must be called by current admin)
+        UpdateConfig {
+            default_timeout: u64,
+            default_gas_limit: Option<u64>,
+            gov_contract: String,
+        },
     }
 
     #[derive(Decode, Encode)]
@@ -375,34 +672,74 @@ pub mod my_psp37_wrapper {
         /// Show the Config.
         //#[returns(ConfigResponse)]
         Config {},
-        //#[returns(cw_controllers::AdminResponse)]
+        //#[returns(AdminResponse)]
         Admin {},
-        /// Query if a given cw20 contract is allowed.
+        /// Query if a given PSP22 contract is allowed.
         //#[returns(AllowedResponse)]
         Allowed {
             contract: String,
         },
-        /// List all allowed cw20 contracts.
+        /// List all allowed PSP22 contracts.
         //#[returns(ListAllowedResponse)]
         ListAllowed {
             start_after: Option<String>,
             limit: Option<u32>,
         },
+        /// The IBC base denom a pallet-asset is sent as.
+        //#[returns(Option<String>)]
+        AssetDenom {
+            id: u32,
+        },
+        /// The pallet-asset registered for an IBC base denom.
+        //#[returns(Option<u32>)]
+        DenomAsset {
+            denom: String,
+        },
+        /// Resolves an `ibc/{hash}` voucher denom to its full trace.
+        //#[returns(DenomTrace)]
+        DenomTrace {
+            hash: String,
+        },
+        /// The rate limit of a denom over a channel and how much of it is used.
+        //#[returns(Option<RateLimitResponse>)]
+        RateLimit {
+            channel: String,
+            denom: String,
+        },
+        /// The next send, receive and ack sequence of a channel.
+        //#[returns(Option<ChannelSequences>)]
+        Sequences {
+            channel_id: String,
+        },
     }
 
-    #[derive(Decode, Encode, Default)]
+    #[derive(Debug, Decode, Encode, Default)]
     #[cfg_attr(feature = "std", derive(StorageLayout, scale_info::TypeInfo))]
     pub struct Config {
         pub default_timeout: u64,
         pub default_gas_limit: Option<u64>,
+        /// who can allow more contracts
+        pub gov_contract: Addr,
     }
 
-    #[derive(Decode, Encode, Default)]
+    /// A packet received for forwarding. Its ack is held until the forwarded packet is
+    /// acknowledged or finally times out.
+    #[derive(Debug, Decode, Encode, Clone)]
     #[cfg_attr(feature = "std", derive(StorageLayout, scale_info::TypeInfo))]
-    pub struct ReplyArgs {
+    pub struct HeldPacket {
+        /// our channel the packet came in on, and its sequence there
         pub channel: String,
+        pub sequence: u64,
+        /// whether the funds were unescrowed on receipt, rather than minted as vouchers
+        pub unescrowed: bool,
+        /// the local denom and amount we hold while the packet is in flight
         pub denom: String,
         pub amount: u128,
+        /// where the packet goes next
+        pub forward: ForwardMetadata,
+        /// whether the forwarded packet locked the funds in our escrow for its channel
+        pub forward_escrowed: bool,
+        pub retries_left: u8,
     }
 
     #[derive(Decode, Encode, Default)]
@@ -418,11 +755,96 @@ pub mod my_psp37_wrapper {
         pub total_sent: u128,
     }
 
-    #[derive(Decode, Encode, Deserialize, Serialize)]
+    /// How much of a denom may cross a channel in one direction within a window.
+    #[derive(Decode, Encode, Clone, Copy, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(StorageLayout, scale_info::TypeInfo))]
+    pub enum QuotaLimit {
+        /// a percentage of the channel value taken when the window starts, only for denoms
+        /// that have one
+        Percent(u32),
+        /// a fixed amount
+        Absolute(u128),
+    }
+
+    /// Inflow and outflow quotas of a channel and denom, unlimited if unset.
+    #[derive(Decode, Encode, Clone, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(StorageLayout, scale_info::TypeInfo))]
+    pub struct Quota {
+        /// length of a window in seconds, the flows start over when it ends
+        pub duration: u64,
+        pub inflow: Option<QuotaLimit>,
+        pub outflow: Option<QuotaLimit>,
+    }
+
+    /// A quota and the flows counted against it in the current window.
+    #[derive(Decode, Encode, Clone)]
     #[cfg_attr(feature = "std", derive(StorageLayout, scale_info::TypeInfo))]
-    pub enum Ics20Ack {
-        Result(Vec<u8>),
-        Error(String),
+    pub struct RateLimit {
+        pub quota: Quota,
+        /// block time in milliseconds
+        pub window_start: u64,
+        pub channel_value: u128,
+        pub inflow: u128,
+        pub outflow: u128,
+    }
+
+    impl RateLimit {
+        fn window_end(&self) -> u64 {
+            self.window_start
+                .saturating_add(self.quota.duration.saturating_mul(1000))
+        }
+
+        /// The amount a limit allows in this window.
+        fn allowance(&self, limit: Option<QuotaLimit>) -> Option<u128> {
+            match limit {
+                Some(QuotaLimit::Percent(percent)) => {
+                    Some(self.channel_value.saturating_mul(percent.into()) / 100)
+                }
+                Some(QuotaLimit::Absolute(amount)) => Some(amount),
+                None => None,
+            }
+        }
+
+        fn inflow_left(&self) -> Option<u128> {
+            self.allowance(self.quota.inflow)
+                .map(|allowed| allowed.saturating_sub(self.inflow))
+        }
+
+        fn outflow_left(&self) -> Option<u128> {
+            self.allowance(self.quota.outflow)
+                .map(|allowed| allowed.saturating_sub(self.outflow))
+        }
+    }
+
+    /// The direction tokens cross a channel in.
+    #[derive(Clone, Copy)]
+    enum Flow {
+        In,
+        Out,
+    }
+
+    /// ICS-20 uses the standard ICS-04 acknowledgement envelope.
+    pub type Ics20Ack = StdAck;
+
+    /// The success ack written by ibc-go's transfer module: `{"result":"AQ=="}`.
+    pub fn ack_success() -> Ics20Ack {
+        Ics20Ack::success([1u8])
+    }
+
+    pub fn ack_fail(err: &Error) -> Ics20Ack {
+        Ics20Ack::from_error(err)
+    }
+
+    /// Strips the `{port}/{channel}/` prefix the remote chain put on a voucher of one of our
+    /// tokens. Fails unless the voucher was minted for the given endpoint.
+    pub fn parse_voucher_denom<'a>(
+        voucher_denom: &'a str,
+        remote_endpoint: &IbcEndpoint,
+    ) -> Result<&'a str, Error> {
+        voucher_denom
+            .strip_prefix(&denom_prefix(&remote_endpoint.port_id, &remote_endpoint.channel_id))
+            .filter(|base| !base.is_empty())
+            .ok_or(Error::NoForeignTokens {})
     }
 
     #[derive(Decode, Encode, Serialize, Deserialize)]
@@ -526,7 +948,7 @@ pub mod my_psp37_wrapper {
         /// #[error("Didn't send any funds")]
         NoFunds {},
 
-        /// #[error("Amount larger than 2**64, not supported by ics20 packets")]
+        /// #[error("Amount larger than 2**128, not supported by ics20 packets")]
         AmountOverflow {},
 
         /// #[error("Only supports channel with ibc version ics20-1, got {version}")]
@@ -562,7 +984,7 @@ pub mod my_psp37_wrapper {
         /// #[error("Only the governance contract can do this")]
         Unauthorized,
 
-        /// #[error("You can only send cw20 tokens that have been explicitly allowed by governance")]
+        /// #[error("You can only send PSP22 tokens that have been explicitly allowed by governance")]
         NotOnAllowList,
 
         /// # channelinfo not found for channel_id
@@ -570,11 +992,365 @@ pub mod my_psp37_wrapper {
 
         /// # channel Denom not found for channel_id
         ChannelTokenDenomNotFound,
+
+        /// #[error("No denomination trace found for {denom}")]
+        DenomTraceNotFound { denom: String },
+
+        /// #[error("Transfer timeout must be set and fit into an IBC timestamp")]
+        InvalidTimeout {},
+
+        /// #[error("Failed to send tokens: {reason}")]
+        SendFailed { reason: String },
+
+        /// #[error("Not a contract address: {address}")]
+        InvalidAddress { address: String },
+
+        /// #[error("Asset {id} is not registered for IBC transfers")]
+        UnknownAsset { id: u32 },
+
+        /// #[error("Asset {id} is already registered")]
+        AssetAlreadyRegistered { id: u32 },
+
+        /// #[error("Denom {denom} already belongs to another asset")]
+        DenomAlreadyRegistered { denom: String },
+
+        /// #[error("Assets can only be registered under a base denom, got {denom}")]
+        InvalidDenom { denom: String },
+
+        /// #[error("IBC chain extension failed: {0}")]
+        ChainExtension(IBCICS20Error),
+
+        /// #[error("Channel {id} is not open")]
+        ChannelNotOpen { id: String },
+
+        /// #[error("Invalid ink hook: {reason}")]
+        InvalidHook { reason: String },
+
+        /// #[error("Cannot forward packet: {reason}")]
+        InvalidForward { reason: String },
+
+        /// #[error("Rate limit exceeded for {denom} on {channel}")]
+        RateLimitExceeded { channel: String, denom: String },
+
+        /// #[error("Invalid quota: {reason}")]
+        InvalidQuota { reason: String },
+
+        /// #[error("Ink hook call failed: {reason}")]
+        HookFailed { reason: String },
+
+        /// #[error("Only the caller's own tokens can be sent, not those of {sender}")]
+        InvalidSender { sender: String },
+    }
+
+    impl From<IBCICS20Error> for Error {
+        fn from(err: IBCICS20Error) -> Self {
+            Error::ChainExtension(err)
+        }
+    }
+
+    impl From<trait_ibc::ibc::Error> for Error {
+        fn from(err: trait_ibc::ibc::Error) -> Self {
+            Error::IBCError(err)
+        }
     }
 
     impl core::fmt::Display for Error {
-        fn fmt(&self, _f: &mut Formatter<'_>) -> core::fmt::Result {
-            Ok(())
+        fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+            match self {
+                Error::IBCError(err) => write!(f, "{}", err),
+                Error::StdError => write!(f, "Std error"),
+                Error::PaymentError => write!(f, "Invalid funds sent"),
+                Error::AdminError => write!(f, "Caller is not admin"),
+                Error::NoSuchChannel { id } => write!(f, "Channel doesn't exist: {}", id),
+                Error::NoFunds {} => write!(f, "Didn't send any funds"),
+                Error::AmountOverflow {} => {
+                    write!(f, "Amount larger than 2**128, not supported by ics20 packets")
+                }
+                Error::InvalidIbcVersion { version } => write!(
+                    f,
+                    "Only supports channel with ibc version ics20-1, got {}",
+                    version
+                ),
+                Error::OnlyOrderedChannel {} => write!(f, "Only supports unordered channel"),
+                Error::InsufficientFunds {} => {
+                    write!(f, "Insufficient funds to redeem voucher on channel")
+                }
+                Error::NoForeignTokens {} => write!(
+                    f,
+                    "Only accepts tokens that originate on this chain, not native tokens of remote chain"
+                ),
+                Error::FromOtherPort { port } => {
+                    write!(f, "Parsed port from denom ({}) doesn't match packet", port)
+                }
+                Error::FromOtherChannel { channel } => {
+                    write!(f, "Parsed channel from denom ({}) doesn't match packet", channel)
+                }
+                Error::CannotMigrate { previous_contract } => write!(
+                    f,
+                    "Cannot migrate from different contract type: {}",
+                    previous_contract
+                ),
+                Error::CannotMigrateVersion { previous_version } => write!(
+                    f,
+                    "Cannot migrate from unsupported version: {}",
+                    previous_version
+                ),
+                Error::UnknownReplyId { id } => {
+                    write!(f, "Got a submessage reply with unknown id: {}", id)
+                }
+                Error::CannotLowerGas => write!(
+                    f,
+                    "You cannot lower the gas limit for a contract on the allow list"
+                ),
+                Error::Unauthorized => write!(f, "Only the governance contract can do this"),
+                Error::NotOnAllowList => write!(
+                    f,
+                    "You can only send PSP22 tokens that have been explicitly allowed by governance"
+                ),
+                Error::ChannelInfoNotFound => write!(f, "Channel info not found"),
+                Error::ChannelTokenDenomNotFound => write!(f, "Channel denoms not found"),
+                Error::DenomTraceNotFound { denom } => {
+                    write!(f, "No denomination trace found for {}", denom)
+                }
+                Error::InvalidTimeout {} => {
+                    write!(f, "Transfer timeout must be set and fit into an IBC timestamp")
+                }
+                Error::SendFailed { reason } => write!(f, "Failed to send tokens: {}", reason),
+                Error::InvalidAddress { address } => {
+                    write!(f, "Not a contract address: {}", address)
+                }
+                Error::UnknownAsset { id } => {
+                    write!(f, "Asset {} is not registered for IBC transfers", id)
+                }
+                Error::AssetAlreadyRegistered { id } => {
+                    write!(f, "Asset {} is already registered", id)
+                }
+                Error::DenomAlreadyRegistered { denom } => {
+                    write!(f, "Denom {} already belongs to another asset", denom)
+                }
+                Error::InvalidDenom { denom } => write!(
+                    f,
+                    "Assets can only be registered under a base denom, got {}",
+                    denom
+                ),
+                Error::ChainExtension(err) => write!(
+                    f,
+                    "IBC chain extension failed: {} (status {})",
+                    err,
+                    err.status_code()
+                ),
+                Error::ChannelNotOpen { id } => write!(f, "Channel {} is not open", id),
+                Error::InvalidHook { reason } => write!(f, "Invalid ink hook: {}", reason),
+                Error::InvalidForward { reason } => write!(f, "Cannot forward packet: {}", reason),
+                Error::RateLimitExceeded { channel, denom } => {
+                    write!(f, "Rate limit exceeded for {} on {}", denom, channel)
+                }
+                Error::InvalidQuota { reason } => write!(f, "Invalid quota: {}", reason),
+                Error::HookFailed { reason } => write!(f, "Ink hook call failed: {}", reason),
+                Error::InvalidSender { sender } => {
+                    write!(f, "Only the caller's own tokens can be sent, not those of {}", sender)
+                }
+            }
+        }
+    }
+
+    /// ics20 errors use codes from 100 on; wrapped trait-ibc errors keep their own code.
+    impl AckError for Error {
+        fn code(&self) -> u32 {
+            match self {
+                Error::IBCError(err) => err.code(),
+                Error::StdError => 100,
+                Error::PaymentError => 101,
+                Error::AdminError => 102,
+                Error::NoSuchChannel { .. } => 103,
+                Error::NoFunds {} => 104,
+                Error::AmountOverflow {} => 105,
+                Error::InvalidIbcVersion { .. } => 106,
+                Error::OnlyOrderedChannel {} => 107,
+                Error::InsufficientFunds {} => 108,
+                Error::NoForeignTokens {} => 109,
+                Error::FromOtherPort { .. } => 110,
+                Error::FromOtherChannel { .. } => 111,
+                Error::CannotMigrate { .. } => 112,
+                Error::CannotMigrateVersion { .. } => 113,
+                Error::UnknownReplyId { .. } => 114,
+                Error::CannotLowerGas => 115,
+                Error::Unauthorized => 116,
+                Error::NotOnAllowList => 117,
+                Error::ChannelInfoNotFound => 118,
+                Error::ChannelTokenDenomNotFound => 119,
+                Error::DenomTraceNotFound { .. } => 120,
+                Error::InvalidTimeout {} => 121,
+                Error::SendFailed { .. } => 122,
+                Error::InvalidAddress { .. } => 123,
+                Error::UnknownAsset { .. } => 124,
+                Error::AssetAlreadyRegistered { .. } => 125,
+                Error::DenomAlreadyRegistered { .. } => 126,
+                Error::InvalidDenom { .. } => 127,
+                Error::ChainExtension(_) => 128,
+                Error::ChannelNotOpen { .. } => 129,
+                Error::InvalidHook { .. } => 130,
+                Error::InvalidForward { .. } => 131,
+                Error::RateLimitExceeded { .. } => 132,
+                Error::InvalidQuota { .. } => 133,
+                Error::HookFailed { .. } => 134,
+                Error::InvalidSender { .. } => 135,
+            }
+        }
+    }
+
+    /// The admin handed the contract over to someone else.
+    #[ink(event)]
+    pub struct AdminUpdated {
+        #[ink(topic)]
+        previous: Addr,
+        #[ink(topic)]
+        admin: Addr,
+    }
+
+    /// The admin replaced the config.
+    #[ink(event)]
+    pub struct ConfigUpdated {
+        default_timeout: u64,
+        default_gas_limit: Option<u64>,
+        #[ink(topic)]
+        gov_contract: Addr,
+    }
+
+    /// A channel finished its handshake with us.
+    #[ink(event)]
+    pub struct ChannelOpened {
+        #[ink(topic)]
+        channel: String,
+        #[ink(topic)]
+        counterparty_port: String,
+        counterparty_channel: String,
+        connection_id: String,
+        version: String,
+    }
+
+    /// The counterparty closed a channel.
+    #[ink(event)]
+    pub struct ChannelClosed {
+        #[ink(topic)]
+        channel: String,
+    }
+
+    /// A transfer packet went out, including packets we forward.
+    #[ink(event)]
+    pub struct TransferSent {
+        #[ink(topic)]
+        channel: String,
+        #[ink(topic)]
+        sequence: u64,
+        #[ink(topic)]
+        sender: String,
+        receiver: String,
+        /// the denom as it travels, with its full trace
+        denom: String,
+        amount: u128,
+    }
+
+    /// A transfer packet came in. `error` is set if it was answered with an error ack.
+    #[ink(event)]
+    pub struct TransferReceived {
+        #[ink(topic)]
+        channel: String,
+        #[ink(topic)]
+        sequence: u64,
+        #[ink(topic)]
+        receiver: String,
+        sender: String,
+        denom: String,
+        amount: u128,
+        error: Option<String>,
+    }
+
+    /// The counterparty acknowledged a transfer we sent, `error` is set for an error ack.
+    #[ink(event)]
+    pub struct TransferAcknowledged {
+        #[ink(topic)]
+        channel: String,
+        #[ink(topic)]
+        sequence: u64,
+        #[ink(topic)]
+        sender: String,
+        receiver: String,
+        denom: String,
+        amount: u128,
+        error: Option<String>,
+    }
+
+    /// A transfer we sent timed out.
+    #[ink(event)]
+    pub struct TransferTimedOut {
+        #[ink(topic)]
+        channel: String,
+        #[ink(topic)]
+        sequence: u64,
+        #[ink(topic)]
+        sender: String,
+        receiver: String,
+        denom: String,
+        amount: u128,
+    }
+
+    /// The tokens of a failed transfer went back to its sender.
+    #[ink(event)]
+    pub struct TransferRefunded {
+        #[ink(topic)]
+        channel: String,
+        #[ink(topic)]
+        sequence: u64,
+        #[ink(topic)]
+        sender: String,
+        /// the local denom
+        denom: String,
+        amount: u128,
+    }
+
+    /// Governance allowed a PSP22 contract, or raised its gas limit.
+    #[ink(event)]
+    pub struct ContractAllowed {
+        #[ink(topic)]
+        contract: Addr,
+        gas_limit: Option<u64>,
+    }
+
+    /// Governance linked a pallet-asset to its IBC base denom.
+    #[ink(event)]
+    pub struct AssetRegistered {
+        #[ink(topic)]
+        id: u32,
+        #[ink(topic)]
+        denom: String,
+    }
+
+    /// Governance set the quota of a channel and denom, `None` when it was lifted.
+    #[ink(event)]
+    pub struct QuotaUpdated {
+        #[ink(topic)]
+        channel: String,
+        #[ink(topic)]
+        denom: String,
+        quota: Option<Quota>,
+    }
+
+    /// Governance started a fresh window for a quota.
+    #[ink(event)]
+    pub struct QuotaReset {
+        #[ink(topic)]
+        channel: String,
+        #[ink(topic)]
+        denom: String,
+    }
+
+    /// Addresses we hand to the runtime or compare against callers must be contract accounts.
+    /// They are kept in the lowercase form `Addr::from` gives, so equal accounts compare equal.
+    fn checked_addr(address: String) -> Result<Addr, Error> {
+        match Addr::unchecked(address.clone()).to_account_id() {
+            Some(account) => Ok(Addr::from(account)),
+            None => Err(Error::InvalidAddress { address }),
         }
     }
 
@@ -585,197 +1361,3071 @@ pub mod my_psp37_wrapper {
         admin: Addr,
         /// isc20_config
         config: Config,
-        /// Used to pass info from the ibc_packet_receive to the reply handler
-        reply_args: ReplyArgs,
         /// static info on one channel that doesn't change
         channel_info: Mapping<String, ChannelInfo>,
         /// channel_token_denom list
         channel_token_denom: Mapping<String, Vec<String>>,
         /// indexed by (channel_id, denom) maintaining the balance of the channel in that currency
         channel_state: Mapping<(String, String), ChannelState>,
-        /// Every cw20 contract we allow to be sent is stored here, possibly with a gas_limit
+        /// Every PSP22 contract we allow to be sent is stored here, possibly with a gas_limit
         allow_list: Mapping<Addr, AllowInfo>,
+        /// keys of `allow_list` in ascending order, for paginated listing
+        allowed_contracts: Lazy<Vec<Addr>>,
+        /// IBC base denom of every pallet-asset that can be bridged, managed by gov_contract
+        asset_denoms: Mapping<u32, String>,
+        /// reverse index of `asset_denoms`
+        denom_assets: Mapping<String, u32>,
+        /// packets we forwarded, by our channel and sequence, holding the ack of the inbound packet
+        in_flight: Mapping<(String, u64), HeldPacket>,
+        /// traces of all vouchers we have minted, indexed by their `ibc/{hash}`
+        denom_traces: DenomTraces,
+        /// indexed by (channel_id, denom), quotas set by gov_contract and their usage
+        rate_limits: Mapping<(String, String), RateLimit>,
+        /// next send, receive and ack sequence of every channel
+        sequences: SequenceTracker,
     }
 
     // impl PSP37 for Contract {}
 
-    impl Contract {
-        #[ink(constructor)]
-        pub fn new() -> Self {
-            let instance = Self::default();
-
-            instance
+    impl BaseIbc for Contract {
+        #[ink(message)]
+        fn reply(&mut self, reply: Reply) -> Result<Response, trait_ibc::ibc::Error> {
+            // tokens are minted and paid synchronously, we send no submessages
+            Err(trait_ibc::ibc::Error::UnknownReplyId { id: reply.id })
         }
 
-        // set function list
-        /// transfer token, This allows us to transfer *exactly one* native token
         #[ink(message)]
-        pub fn execute_transfer(
-            &mut self,
-            msg: TransferMsg,
-            amount: Amount,
-            sender: Addr,
-        ) -> Result<Response, Error> {
-            // construct MsgTransfer
-
-            match amount {
-                Amount::Native(coin) => {
-                    let timestamp = self.env().block_timestamp().saturating_add(60 * 1000); //microsecond
-                    let source_channel = msg.channel;
-                    let denom = coin.denom;
-                    let amount = coin.amount;
-                    let sender = sender.into_string();
-                    let receiver = msg.remote_address;
-                    let _rt = self.env().extension().raw_tranfer(
-                        source_channel.into(),
-                        denom.into(),
-                        amount.to_string().into(),
-                        sender.into(),
-                        receiver.into(),
-                        timestamp,
-                        Default::default(),
-                    );
-                }
-                _ => {}
-            }
-
-            Ok(Response {
-                messages: Vec::new(),
-                attributes: Vec::new(),
-                events: Vec::new(),
-                data: None,
-            })
+        fn migrate(&self, _msg: Empty) -> Result<Response, trait_ibc::ibc::Error> {
+            Ok(Response::new())
         }
 
-        // query function list
-
-        /// Return the port ID bound by this contract.
         #[ink(message)]
-        pub fn query_port(&self) -> Result<PortResponse, Error> {
-            Ok(PortResponse {
-                port_id: PortId::transfer().to_string(),
-            })
+        fn ibc_channel_open(
+            &self,
+            msg: IbcChannelOpenMsg,
+        ) -> Result<IbcChannelOpenResponse, trait_ibc::ibc::Error> {
+            handshake_policy().open_response(&msg)
         }
 
-        // PSP37 interface queries
-
-        /// Returns the account balance for the specified asset & owner.
+        /// Records the channel once the handshake is complete.
         #[ink(message)]
-        pub fn balance_of(&self, owner: AccountId, id: Option<u32>) -> Balance {
-            if id.is_none() {
-                return Balance::default();
-            }
-
-            let rt = self
-                .env()
-                .extension()
-                .balance_of(owner, id)
-                .map_err(|e| PSP37Error::Custom(e.into()));
-            match rt {
-                Ok(balance) => balance,
-                _ => Balance::default(),
-            }
+        fn ibc_channel_connect(
+            &mut self,
+            msg: IbcChannelConnectMsg,
+        ) -> Result<IbcBasicResponse, trait_ibc::ibc::Error> {
+            handshake_policy().validate_connect(&msg)?;
+            let channel = msg.channel();
+            let info = ChannelInfo {
+                id: channel.endpoint.channel_id.clone(),
+                counterparty_endpoint: channel.counterparty_endpoint.clone(),
+                connection_id: channel.connection_id.clone(),
+            };
+            self.channel_info.insert(&info.id, &info);
+            self.sequences.open(&msg);
+            self.env().emit_event(ChannelOpened {
+                channel: info.id.clone(),
+                counterparty_port: channel.counterparty_endpoint.port_id.clone(),
+                counterparty_channel: channel.counterparty_endpoint.channel_id.clone(),
+                connection_id: channel.connection_id.clone(),
+                version: channel.version.clone(),
+            });
+            Ok(IbcBasicResponse::new()
+                .add_attribute("action", "ibc_connect")
+                .add_attribute("channel_id", info.id))
         }
 
-        /// Returns the total token supply of the specified asset.
+        /// Like ibc-go, escrowed funds must not get stuck, so channels can't be closed by us.
         #[ink(message)]
-        pub fn total_supply(&self, id: Option<u32>) -> Balance {
-            if id.is_none() {
-                return Balance::default();
+        fn ibc_channel_close(
+            &self,
+            msg: IbcChannelCloseMsg,
+        ) -> Result<IbcBasicResponse, trait_ibc::ibc::Error> {
+            match msg {
+                IbcChannelCloseMsg::CloseInit { .. } => {
+                    Err(trait_ibc::ibc::Error::CannotCloseChannel)
+                }
+                IbcChannelCloseMsg::CloseConfirm { channel } => {
+                    let channel = channel.endpoint.channel_id;
+                    self.env().emit_event(ChannelClosed {
+                        channel: channel.clone(),
+                    });
+                    Ok(IbcBasicResponse::new()
+                        .add_attribute("action", "ibc_close")
+                        .add_attribute("channel_id", channel))
+                }
             }
+        }
 
-            let rt = self
-                .env()
-                .extension()
-                .total_supply(id)
-                .map_err(|e| PSP37Error::Custom(e.into()));
-            match rt {
-                Ok(total_supply) => total_supply,
-                _ => Balance::default(),
+        /// Check to see if we have any balance here.
+        /// We should not return an error if possible, but rather an acknowledgement of failure.
+        #[ink(message)]
+        fn ibc_packet_receive(
+            &mut self,
+            msg: IbcPacketReceiveMsg,
+        ) -> Result<IbcReceiveResponse, trait_ibc::ibc::Error> {
+            let packet = msg.packet;
+            // counted even if it is answered with an error ack
+            self.sequences.on_receive(&packet)?;
+            let res = self.do_ibc_packet_receive(&packet);
+            if let Ok(data) = Ics20Packet::from_json(&packet.data) {
+                self.env().emit_event(TransferReceived {
+                    channel: packet.dest.channel_id.clone(),
+                    sequence: packet.sequence,
+                    receiver: data.receiver,
+                    sender: data.sender,
+                    denom: data.denom,
+                    amount: data.amount,
+                    error: res.as_ref().err().map(|err| err.to_string()),
+                });
             }
+            Ok(res.unwrap_or_else(|err| {
+                IbcReceiveResponse::new()
+                    .set_ack(ack_fail(&err))
+                    .add_attribute("action", "receive")
+                    .add_attribute("success", "false")
+                    .add_attribute("error", err.to_string())
+            }))
         }
 
-        /// Returns the amount which `spender` is still allowed to withdraw from `owner`
-        /// for the specified asset.
+        /// Refunds the sender if the counterparty failed to process the packet.
         #[ink(message)]
-        pub fn allowance(&self, owner: AccountId, spender: AccountId, id: Option<u32>) -> Balance {
-            if id.is_none() {
-                return Balance::default();
+        fn ibc_packet_ack(
+            &mut self,
+            msg: IbcPacketAckMsg,
+        ) -> Result<IbcBasicResponse, trait_ibc::ibc::Error> {
+            let packet = &msg.original_packet;
+            self.sequences.on_ack(packet)?;
+            let ack = msg.acknowledgement.std_ack()?;
+            let data = Ics20Packet::from_json(&packet.data)?;
+            self.env().emit_event(TransferAcknowledged {
+                channel: packet.src.channel_id.clone(),
+                sequence: packet.sequence,
+                sender: data.sender,
+                receiver: data.receiver,
+                denom: data.denom,
+                amount: data.amount,
+                error: match &ack {
+                    StdAck::Result(_) => None,
+                    StdAck::Error(err) => Some(err.clone()),
+                },
+            });
+            // a forwarded packet settles the packet we received for it
+            let key = (packet.src.channel_id.clone(), packet.sequence);
+            if let Some(held) = self.in_flight.take(&key) {
+                let msgs = match ack {
+                    StdAck::Result(_) => vec![write_ack(&held, msg.acknowledgement.clone())],
+                    StdAck::Error(reason) => {
+                        let err = Error::SendFailed { reason };
+                        self.release_forward(&held)?;
+                        self.unwind_held(&held, &err)?
+                    }
+                };
+                let mut res = IbcBasicResponse::new().add_attribute("action", "forward_ack");
+                for msg in msgs {
+                    res = res.add_message(msg);
+                }
+                return Ok(res);
             }
-
-            let rt = self
-                .env()
-                .extension()
-                .allowance(owner, spender, id)
-                .map_err(|e| PSP37Error::Custom(e.into()));
-            match rt {
-                Ok(allowance) => allowance,
-                _ => Balance::default(),
+            match ack {
+                StdAck::Result(_) => on_packet_success(packet),
+                StdAck::Error(err) => self.on_packet_failure(packet, err),
             }
         }
 
-        // PSP37 approve
-
-        /// Allows `spender` to withdraw from the caller's account multiple times, up to
-        /// the `value` amount of the specified asset.
+        /// Refunds the sender, the packet will never be received.
         #[ink(message)]
-        pub fn approve(
+        fn ibc_packet_timeout(
             &mut self,
-            spender: AccountId,
-            id: Option<u32>,
-            value: Balance,
-        ) -> Result<(), PSP37Error> {
-            if id.is_none() {
-                return Ok(());
+            msg: IbcPacketTimeoutMsg,
+        ) -> Result<IbcBasicResponse, trait_ibc::ibc::Error> {
+            let packet = &msg.packet;
+            self.sequences.on_timeout(packet)?;
+            let data = Ics20Packet::from_json(&packet.data)?;
+            self.env().emit_event(TransferTimedOut {
+                channel: packet.src.channel_id.clone(),
+                sequence: packet.sequence,
+                sender: data.sender,
+                receiver: data.receiver,
+                denom: data.denom,
+                amount: data.amount,
+            });
+            let key = (packet.src.channel_id.clone(), packet.sequence);
+            if let Some(mut held) = self.in_flight.take(&key) {
+                let mut res = IbcBasicResponse::new().add_attribute("action", "forward_timeout");
+                self.release_forward(&held)?;
+                // the funds are back with us and can go again
+                if held.retries_left > 0 {
+                    held.retries_left -= 1;
+                    if let Ok(sequence) = self.forward_held(held.clone()) {
+                        return Ok(res.add_attribute("retry", sequence.to_string()));
+                    }
+                }
+                let err = trait_ibc::ibc::Error::TimeoutError.into();
+                for msg in self.unwind_held(&held, &err)? {
+                    res = res.add_message(msg);
+                }
+                return Ok(res);
             }
+            self.on_packet_failure(packet, "timeout".to_string())
+        }
+    }
 
-            let _ = self
-                .env()
-                .extension()
-                .approve(spender, id, value)
-                .map_err(|e| PSP37Error::Custom(e.into()))?;
-            Ok(())
+    /// Writes the ack of a held packet, passing on the ack of the hop after us.
+    fn write_ack(held: &HeldPacket, ack: IbcAcknowledgement) -> CosmosMsg<Empty> {
+        IbcMsg::WriteAcknowledgement {
+            channel_id: held.channel.clone(),
+            packet_sequence: held.sequence,
+            ack,
         }
+        .into()
+    }
 
-        // PSP37 transfer
+    fn on_packet_success(packet: &IbcPacket) -> Result<IbcBasicResponse, trait_ibc::ibc::Error> {
+        let msg = Ics20Packet::from_json(&packet.data)?;
+        Ok(IbcBasicResponse::new()
+            .add_attribute("action", "acknowledge")
+            .add_attribute("sender", msg.sender)
+            .add_attribute("receiver", msg.receiver)
+            .add_attribute("denom", msg.denom)
+            .add_attribute("amount", msg.amount.to_string())
+            .add_attribute("success", "true"))
+    }
 
-        /// Transfers `value` amount of specified asset from the caller's account to the
-        /// account `to`.
-        #[ink(message)]
-        pub fn transfer(
+    impl Contract {
+        #[ink(constructor)]
+        pub fn new(msg: InitMsg) -> Result<Self, Error> {
+            if msg.default_timeout == 0 {
+                return Err(Error::InvalidTimeout {});
+            }
+            let mut instance = Self::default();
+            instance.admin = Addr::from(instance.env().caller());
+            instance.config = Config {
+                default_timeout: msg.default_timeout,
+                default_gas_limit: msg.default_gas_limit,
+                gov_contract: checked_addr(msg.gov_contract)?,
+            };
+            for allow in msg.allowlist {
+                instance.add_allowed(allow)?;
+            }
+
+            Ok(instance)
+        }
+
+        /// Change the admin (must be called by current admin)
+        #[ink(message)]
+        pub fn update_admin(&mut self, admin: String) -> Result<(), Error> {
+            self.ensure_admin()?;
+            let admin = checked_addr(admin)?;
+            let previous = core::mem::replace(&mut self.admin, admin.clone());
+            self.env().emit_event(AdminUpdated { previous, admin });
+            Ok(())
+        }
+
+        /// Replace the transfer defaults and the governance contract (must be called by admin)
+        #[ink(message)]
+        pub fn update_config(
             &mut self,
-            to: AccountId,
-            id: u32,
-            value: Balance,
-            _data: Vec<u8>,
-        ) -> Result<(), PSP37Error> {
-            let _ = self
-                .env()
-                .extension()
-                .transfer(to, id, value)
-                .map_err(|e| PSP37Error::Custom(e.into()))?;
+            default_timeout: u64,
+            default_gas_limit: Option<u64>,
+            gov_contract: String,
+        ) -> Result<(), Error> {
+            self.ensure_admin()?;
+            if default_timeout == 0 {
+                return Err(Error::InvalidTimeout {});
+            }
+            let gov_contract = checked_addr(gov_contract)?;
+            self.config = Config {
+                default_timeout,
+                default_gas_limit,
+                gov_contract: gov_contract.clone(),
+            };
+            self.env().emit_event(ConfigUpdated {
+                default_timeout,
+                default_gas_limit,
+                gov_contract,
+            });
             Ok(())
         }
 
-        // PSP37 transfer_from
+        fn ensure_admin(&self) -> Result<(), Error> {
+            if Addr::from(self.env().caller()) != self.admin {
+                return Err(Error::AdminError);
+            }
+            Ok(())
+        }
 
-        /// Transfers `value` amount of specified asset on the behalf of `from` to the
-        /// account `to`.
+        // set function list
+        /// transfer token, This allows us to transfer *exactly one* native token.
+        /// The response data is the SCALE encoded sequence of the packet.
         #[ink(message)]
-        pub fn transfer_from(
+        pub fn execute_transfer(
+            &mut self,
+            msg: TransferMsg,
+            amount: Amount,
+            sender: Addr,
+        ) -> Result<Response, Error> {
+            if amount.is_empty() {
+                return Err(Error::NoFunds {});
+            }
+            // the tokens are escrowed or burnt from the caller, they must be the caller's
+            let caller = self.env().caller();
+            if sender.to_account_id() != Some(caller) {
+                return Err(Error::InvalidSender {
+                    sender: sender.into_string(),
+                });
+            }
+            let gas_limit = self.check_gas_limit(&amount)?;
+            self.ensure_channel_open(&msg.channel)?;
+            let timeout = self.timeout_timestamp(msg.timeout)?;
+            self.consume_flow(&msg.channel, &amount.denom(), amount.amount(), Flow::Out)?;
+
+            // contract tokens are pulled into escrow first, the IBC pallet escrows or burns
+            // native tokens and pallet-assets itself
+            if let Amount::Psp22(coin) = &amount {
+                self.psp22_transfer_from(coin, caller, gas_limit)?;
+            }
+            // the pallet is handed the caller's address as we render it, not the spelling passed
+            let sender = Addr::from(caller);
+
+            // construct MsgTransfer
+            let source_channel = msg.channel;
+            let local_denom = amount.denom();
+            // vouchers travel under their full path, not the local ibc/{hash}
+            let denom = self.full_denom_path(&local_denom)?;
+            let amount = amount.amount();
+            let receiver = msg.remote_address;
+            let port_id = PortId::transfer().to_string();
+            let escrowed = sender_chain_is_source(&port_id, &source_channel, &denom);
+            let timeout_height = match msg.timeout_height {
+                TimeoutHeight::Never => None,
+                TimeoutHeight::At(height) => Some(IbcTimeoutBlock {
+                    revision: height.revision_number(),
+                    height: height.revision_height(),
+                }),
+            };
+            let memo = msg.memo.unwrap_or_default();
+            let sequence = self
+                .env()
+                .extension()
+                .send_transfer(
+                    source_channel.clone().into(),
+                    denom.clone().into(),
+                    amount.to_string().into(),
+                    sender.as_bytes().to_vec(),
+                    receiver.clone().into(),
+                    timeout_height,
+                    timeout.nanos(),
+                    memo.into(),
+                )
+                .map_err(|err| {
+                    Error::IBCError(trait_ibc::ibc::Error::SendPacketError {
+                        code: err.status_code(),
+                        reason: err.into(),
+                    })
+                })?;
+            self.sequences.on_send(&source_channel, sequence);
+            self.env().emit_event(TransferSent {
+                channel: source_channel.clone(),
+                sequence,
+                sender: sender.as_str().to_string(),
+                receiver: receiver.clone(),
+                denom: denom.clone(),
+                amount,
+            });
+            // vouchers are burnt, only our own tokens stay locked for the channel
+            if escrowed {
+                self.increase_channel_balance(&source_channel, &local_denom, amount)?;
+            }
+
+            Ok(Response::new()
+                .set_data(sequence.encode())
+                .add_attribute("action", "transfer")
+                .add_attribute("sender", sender.into_string())
+                .add_attribute("receiver", receiver)
+                .add_attribute("denom", denom)
+                .add_attribute("amount", amount.to_string())
+                .add_attribute("channel", source_channel)
+                .add_attribute("sequence", sequence.to_string()))
+        }
+
+        /// Sends `amount` of the pallet-asset `id` to `receiver` over `channel`, under the base
+        /// denom registered for it. The tokens are escrowed from the caller.
+        #[ink(message)]
+        pub fn transfer_asset(
             &mut self,
-            from: AccountId,
-            to: AccountId,
             id: u32,
-            value: Balance,
-            _data: Vec<u8>,
-        ) -> Result<(), PSP37Error> {
-            let _ = self
+            amount: Balance,
+            channel: String,
+            receiver: String,
+        ) -> Result<Response, Error> {
+            let denom = self
+                .asset_denoms
+                .get(id)
+                .ok_or(Error::UnknownAsset { id })?;
+            let msg = TransferMsg {
+                channel,
+                remote_address: receiver,
+                timeout: None,
+                timeout_height: TimeoutHeight::Never,
+                memo: None,
+            };
+            let sender = Addr::from(self.env().caller());
+            self.execute_transfer(msg, Amount::native(amount, &denom), sender)
+        }
+
+        /// Pays a received transfer to the hook contract named in its memo and calls it. Only
+        /// we call this, from `ibc_packet_receive`, so that a failing hook reverts the payment.
+        /// Tokens coming home are paid out of the escrow of `channel`, vouchers are minted
+        /// when it is `None`.
+        #[ink(message)]
+        pub fn run_hook(
+            &self,
+            contract: String,
+            selector: [u8; 4],
+            args: Vec<u8>,
+            amount: Amount,
+            channel: Option<String>,
+        ) -> Result<(), Error> {
+            if self.env().caller() != self.env().account_id() {
+                return Err(Error::Unauthorized);
+            }
+            match channel {
+                Some(channel) => self.send_amount(amount, contract.clone(), &channel)?,
+                None => self.mint_vouchers(&amount.denom(), amount.amount(), contract.clone())?,
+            }
+            self.call_hook(&contract, selector, &args)
+        }
+
+        /// Links the pallet-asset `id` to the IBC base `denom` it travels as. Must be called
+        /// by gov_contract; a registration is final, tokens may already be escrowed under it.
+        #[ink(message)]
+        pub fn register_asset(&mut self, id: u32, denom: String) -> Result<Response, Error> {
+            if Addr::from(self.env().caller()) != self.config.gov_contract {
+                return Err(Error::Unauthorized);
+            }
+            // vouchers and contract tokens already have their own denoms
+            if denom.is_empty() || denom.contains('/') || denom.starts_with(PSP22_DENOM_PREFIX) {
+                return Err(Error::InvalidDenom { denom });
+            }
+            if self.asset_denoms.contains(id) {
+                return Err(Error::AssetAlreadyRegistered { id });
+            }
+            if self.denom_assets.contains(&denom) {
+                return Err(Error::DenomAlreadyRegistered { denom });
+            }
+            self.asset_denoms.insert(id, &denom);
+            self.denom_assets.insert(&denom, &id);
+            self.env().emit_event(AssetRegistered {
+                id,
+                denom: denom.clone(),
+            });
+
+            Ok(Response::new()
+                .add_attribute("action", "register_asset")
+                .add_attribute("id", id.to_string())
+                .add_attribute("denom", denom))
+        }
+
+        /// Limits how much of the local `denom` may come in and go out over `channel` per
+        /// window. Must be called by gov_contract; replacing a quota starts a fresh window.
+        #[ink(message)]
+        pub fn set_quota(
+            &mut self,
+            channel: String,
+            denom: String,
+            quota: Quota,
+        ) -> Result<Response, Error> {
+            if Addr::from(self.env().caller()) != self.config.gov_contract {
+                return Err(Error::Unauthorized);
+            }
+            if !self.channel_info.contains(&channel) {
+                return Err(Error::NoSuchChannel { id: channel });
+            }
+            if quota.duration == 0 {
+                return Err(Error::InvalidQuota {
+                    reason: "the window must not be empty".into(),
+                });
+            }
+            for limit in [quota.inflow, quota.outflow].into_iter().flatten() {
+                if let QuotaLimit::Percent(percent) = limit {
+                    if percent > 100 {
+                        return Err(Error::InvalidQuota {
+                            reason: format!("{}% is more than the supply", percent),
+                        });
+                    }
+                }
+            }
+            let channel_value = self.channel_value(&channel, &denom);
+            Self::ensure_valued(&quota, channel_value, &channel, &denom)?;
+            self.env().emit_event(QuotaUpdated {
+                channel: channel.clone(),
+                denom: denom.clone(),
+                quota: Some(quota.clone()),
+            });
+            let limit = RateLimit {
+                quota,
+                window_start: self.env().block_timestamp(),
+                channel_value,
+                inflow: 0,
+                outflow: 0,
+            };
+            self.rate_limits
+                .insert((channel.clone(), denom.clone()), &limit);
+
+            Ok(Response::new()
+                .add_attribute("action", "set_quota")
+                .add_attribute("channel", channel)
+                .add_attribute("denom", denom))
+        }
+
+        /// Forgets the flows of the current window and starts a fresh one. Must be called by
+        /// gov_contract.
+        #[ink(message)]
+        pub fn reset_quota(&mut self, channel: String, denom: String) -> Result<Response, Error> {
+            if Addr::from(self.env().caller()) != self.config.gov_contract {
+                return Err(Error::Unauthorized);
+            }
+            let key = (channel.clone(), denom.clone());
+            let mut limit = self.rate_limits.get(&key).ok_or(Error::InvalidQuota {
+                reason: format!("no quota for {} on {}", denom, channel),
+            })?;
+            let channel_value = self.channel_value(&channel, &denom);
+            Self::ensure_valued(&limit.quota, channel_value, &channel, &denom)?;
+            limit.window_start = self.env().block_timestamp();
+            limit.channel_value = channel_value;
+            limit.inflow = 0;
+            limit.outflow = 0;
+            self.rate_limits.insert(&key, &limit);
+            self.env().emit_event(QuotaReset {
+                channel: channel.clone(),
+                denom: denom.clone(),
+            });
+
+            Ok(Response::new()
+                .add_attribute("action", "reset_quota")
+                .add_attribute("channel", channel)
+                .add_attribute("denom", denom))
+        }
+
+        /// Lifts the rate limit of `denom` on `channel`. Must be called by gov_contract.
+        #[ink(message)]
+        pub fn remove_quota(&mut self, channel: String, denom: String) -> Result<Response, Error> {
+            if Addr::from(self.env().caller()) != self.config.gov_contract {
+                return Err(Error::Unauthorized);
+            }
+            let key = (channel.clone(), denom.clone());
+            if self.rate_limits.take(&key).is_none() {
+                return Err(Error::InvalidQuota {
+                    reason: format!("no quota for {} on {}", denom, channel),
+                });
+            }
+            self.env().emit_event(QuotaUpdated {
+                channel: channel.clone(),
+                denom: denom.clone(),
+                quota: None,
+            });
+
+            Ok(Response::new()
+                .add_attribute("action", "remove_quota")
+                .add_attribute("channel", channel)
+                .add_attribute("denom", denom))
+        }
+
+        /// Gives the tokens back to the sender: escrowed native tokens are paid out of the
+        /// channel's escrow account, burnt vouchers are minted again.
+        fn on_packet_failure(
+            &mut self,
+            packet: &IbcPacket,
+            err: String,
+        ) -> Result<IbcBasicResponse, trait_ibc::ibc::Error> {
+            let msg = Ics20Packet::from_json(&packet.data)?;
+            let coin = Coin {
+                denom: DenomTrace::parse(&msg.denom).ibc_denom(),
+                amount: msg.amount,
+            };
+            let escrowed =
+                sender_chain_is_source(&packet.src.port_id, &packet.src.channel_id, &msg.denom);
+            // the tokens never left, they no longer count against the quota
+            self.undo_flow(&packet.src.channel_id, &coin.denom, coin.amount, Flow::Out);
+            self.env().emit_event(TransferRefunded {
+                channel: packet.src.channel_id.clone(),
+                sequence: packet.sequence,
+                sender: msg.sender.clone(),
+                denom: coin.denom.clone(),
+                amount: coin.amount,
+            });
+            if escrowed {
+                self.undo_increase_channel_balance(
+                    &packet.src.channel_id,
+                    &coin.denom,
+                    coin.amount,
+                )?;
+                let amount = Amount::from_parts(coin.denom, coin.amount);
+                self.send_amount(amount, msg.sender.clone(), &packet.src.channel_id)
+                    .map_err(|_| trait_ibc::ibc::Error::PacketAckError)?;
+            } else {
+                self.mint_vouchers(&coin.denom, coin.amount, msg.sender.clone())
+                    .map_err(|_| trait_ibc::ibc::Error::PacketAckError)?;
+            }
+            Ok(IbcBasicResponse::new()
+                .add_attribute("action", "acknowledge")
+                .add_attribute("sender", msg.sender)
+                .add_attribute("receiver", msg.receiver)
+                .add_attribute("denom", msg.denom)
+                .add_attribute("amount", msg.amount.to_string())
+                .add_attribute("success", "false")
+                .add_attribute("error", err))
+        }
+
+        /// Unescrows tokens coming home, or mints vouchers for foreign ones, to the receiver.
+        /// An `ink` memo then calls the receiver with the funds in place.
+        fn do_ibc_packet_receive(&mut self, packet: &IbcPacket) -> Result<IbcReceiveResponse, Error> {
+            let msg = Ics20Packet::from_json(&packet.data)?;
+            let channel = packet.dest.channel_id.clone();
+            if !self.channel_info.contains(&channel) {
+                return Err(Error::NoSuchChannel { id: channel });
+            }
+            if msg.amount == 0 {
+                return Err(Error::NoFunds {});
+            }
+            let hook = match msg.memo.as_deref() {
+                Some(memo) => InkHook::from_memo(memo).map_err(|_| Error::InvalidHook {
+                    reason: "malformed ink memo".into(),
+                })?,
+                None => None,
+            };
+            if let Some(hook) = &hook {
+                if hook.contract != msg.receiver {
+                    return Err(Error::InvalidHook {
+                        reason: "the receiver must be the hook contract".into(),
+                    });
+                }
+            }
+            let forward = match msg.memo.as_deref() {
+                Some(memo) => ForwardMetadata::from_memo(memo).map_err(|_| Error::InvalidForward {
+                    reason: "malformed forward memo".into(),
+                })?,
+                None => None,
+            };
+            if let Some(forward) = &forward {
+                if hook.is_some() {
+                    return Err(Error::InvalidForward {
+                        reason: "cannot forward and call a hook".into(),
+                    });
+                }
+                if forward.port != PortId::transfer().as_str() {
+                    return Err(Error::InvalidForward {
+                        reason: "can only forward over the transfer port".into(),
+                    });
+                }
+                self.ensure_channel_open(&forward.channel)?;
+            }
+            // forwarded funds pass through our account
+            let receiver = match forward {
+                Some(_) => Addr::from(self.env().account_id()).into_string(),
+                None => msg.receiver.clone(),
+            };
+
+            let unescrow =
+                receiver_chain_is_source(&packet.src.port_id, &packet.src.channel_id, &msg.denom);
+            let denom = self.receive_denom(packet, &msg.denom)?;
+            let coin = Coin {
+                denom: denom.clone(),
+                amount: msg.amount,
+            };
+            if unescrow {
+                self.reduce_channel_balance(&channel, &denom, msg.amount)?;
+                if let Err(err) = self.consume_flow(&channel, &denom, msg.amount, Flow::In) {
+                    self.undo_reduce_channel_balance(&channel, &denom, msg.amount)?;
+                    return Err(err);
+                }
+                let amount = Amount::from_parts(coin.denom, coin.amount);
+                let paid = match &hook {
+                    Some(hook) => self.pay_hook(hook, amount, Some(&channel)),
+                    None => self.send_amount(amount, receiver, &channel),
+                };
+                if let Err(err) = paid {
+                    // nothing left the escrow, the error ack must not change our books
+                    self.undo_reduce_channel_balance(&channel, &denom, msg.amount)?;
+                    self.undo_flow(&channel, &denom, msg.amount, Flow::In);
+                    return Err(err);
+                }
+            } else {
+                self.consume_flow(&channel, &denom, msg.amount, Flow::In)?;
+                let paid = match &hook {
+                    Some(hook) => {
+                        let amount = Amount::from_parts(denom.clone(), msg.amount);
+                        self.pay_hook(hook, amount, None)
+                    }
+                    None => self.mint_vouchers(&denom, msg.amount, receiver),
+                };
+                if let Err(err) = paid {
+                    self.undo_flow(&channel, &denom, msg.amount, Flow::In);
+                    return Err(err);
+                }
+            }
+
+            if let Some(forward) = forward {
+                let held = HeldPacket {
+                    channel: packet.dest.channel_id.clone(),
+                    sequence: packet.sequence,
+                    unescrowed: unescrow,
+                    denom: denom.clone(),
+                    amount: msg.amount,
+                    retries_left: forward.retries.unwrap_or_default(),
+                    forward,
+                    forward_escrowed: false,
+                };
+                let res = IbcReceiveResponse::without_ack()
+                    .add_attribute("action", "receive")
+                    .add_attribute("sender", msg.sender)
+                    .add_attribute("denom", denom)
+                    .add_attribute("amount", msg.amount.to_string())
+                    .add_attribute("forward", held.forward.channel.clone());
+                if let Err(err) = self.forward_held(held.clone()) {
+                    self.return_held(&held)?;
+                    return Err(err);
+                }
+                return Ok(res);
+            }
+
+            let mut res = IbcReceiveResponse::new().set_ack(ack_success());
+            if let Some(hook) = hook {
+                res = res.add_attribute("hook", hook.contract);
+            }
+            Ok(res
+                .add_attribute("action", "receive")
+                .add_attribute("sender", msg.sender)
+                .add_attribute("receiver", msg.receiver)
+                .add_attribute("denom", denom)
+                .add_attribute("amount", msg.amount.to_string())
+                .add_attribute("success", "true"))
+        }
+
+        /// Sends a held packet on to its next hop and keeps it in flight until that settles.
+        fn forward_held(&mut self, mut held: HeldPacket) -> Result<u64, Error> {
+            let timeout = match held.forward.timeout {
+                Some(nanos) => IbcTimestamp::from_millis(self.env().block_timestamp())
+                    .and_then(|now| now.plus_nanos(nanos))
+                    .ok_or(Error::InvalidTimeout {})?,
+                None => self.timeout_timestamp(None)?,
+            };
+            let denom = self.full_denom_path(&held.denom)?;
+            let port_id = PortId::transfer().to_string();
+            let channel = held.forward.channel.clone();
+            let sender = Addr::from(self.env().account_id());
+            self.consume_flow(&channel, &held.denom, held.amount, Flow::Out)?;
+            let sent = self
                 .env()
                 .extension()
-                .transfer_from(from, to, id, value)
-                .map_err(|e| PSP37Error::Custom(e.into()))?;
+                .send_transfer(
+                    channel.clone().into(),
+                    denom.clone().into(),
+                    held.amount.to_string().into(),
+                    sender.as_bytes().to_vec(),
+                    held.forward.receiver.clone().into(),
+                    None,
+                    timeout.nanos(),
+                    held.forward.next.clone().unwrap_or_default().into(),
+                );
+            let sequence = match sent {
+                Ok(sequence) => sequence,
+                Err(err) => {
+                    self.undo_flow(&channel, &held.denom, held.amount, Flow::Out);
+                    return Err(Error::IBCError(trait_ibc::ibc::Error::SendPacketError {
+                        code: err.status_code(),
+                        reason: err.into(),
+                    }));
+                }
+            };
+            self.sequences.on_send(&channel, sequence);
+            self.env().emit_event(TransferSent {
+                channel: channel.clone(),
+                sequence,
+                sender: sender.as_str().to_string(),
+                receiver: held.forward.receiver.clone(),
+                denom: denom.clone(),
+                amount: held.amount,
+            });
+            held.forward_escrowed = sender_chain_is_source(&port_id, &channel, &denom);
+            if held.forward_escrowed {
+                self.increase_channel_balance(&channel, &held.denom, held.amount)?;
+            }
+            self.in_flight.insert((channel, sequence), &held);
+            Ok(sequence)
+        }
+
+        /// Takes back the escrow and quota the forwarded packet took, and the funds it sent:
+        /// it failed and they are ours again.
+        fn release_forward(&mut self, held: &HeldPacket) -> Result<(), trait_ibc::ibc::Error> {
+            let channel = &held.forward.channel;
+            let us = Addr::from(self.env().account_id()).into_string();
+            let returned = if held.forward_escrowed {
+                let amount = Amount::from_parts(held.denom.clone(), held.amount);
+                self.send_amount(amount, us, channel)
+            } else {
+                // the vouchers went home and were burnt
+                self.mint_vouchers(&held.denom, held.amount, us)
+            };
+            returned.map_err(|_| trait_ibc::ibc::Error::PacketAckError)?;
+            self.undo_flow(channel, &held.denom, held.amount, Flow::Out);
+            if held.forward_escrowed {
+                self.undo_increase_channel_balance(channel, &held.denom, held.amount)?;
+            }
+            Ok(())
+        }
+
+        /// Refunds along the path: undoes our side of the inbound hop and error acks the
+        /// packet, so the chain before us refunds in turn. A forward that went out has to be
+        /// released first.
+        fn unwind_held(
+            &mut self,
+            held: &HeldPacket,
+            err: &Error,
+        ) -> Result<Vec<CosmosMsg<Empty>>, trait_ibc::ibc::Error> {
+            self.return_held(held)
+                .map_err(|_| trait_ibc::ibc::Error::PacketAckError)?;
+            Ok(vec![write_ack(held, IbcAcknowledgement::new(ack_fail(err)))])
+        }
+
+        /// Gives the funds we hold for a packet back to where they came from: tokens coming
+        /// home go back into escrow for their channel, vouchers we minted are burnt.
+        fn return_held(&mut self, held: &HeldPacket) -> Result<(), Error> {
+            let HeldPacket { channel, denom, amount, .. } = held;
+            if held.unescrowed {
+                self.escrow_amount(Amount::from_parts(denom.clone(), *amount), channel)?;
+                self.undo_reduce_channel_balance(channel, denom, *amount)?;
+            } else {
+                self.burn_vouchers(denom, *amount)?;
+            }
+            self.undo_flow(channel, denom, *amount, Flow::In);
+            Ok(())
+        }
+
+        /// Pays received tokens to the hook contract and calls it. Both happen in a call of our
+        /// own `run_hook`, so a failing hook reverts the payment along with it.
+        fn pay_hook(
+            &self,
+            hook: &InkHook,
+            amount: Amount,
+            channel: Option<&str>,
+        ) -> Result<(), Error> {
+            let result = build_call::<crate::IBCDefaultEnvironment>()
+                .call(self.env().account_id())
+                .call_flags(CallFlags::default().set_allow_reentry(true))
+                .exec_input(
+                    ExecutionInput::new(Selector::new(RUN_HOOK))
+                        .push_arg(&hook.contract)
+                        .push_arg(hook.selector)
+                        .push_arg(hook.args.as_slice())
+                        .push_arg(amount)
+                        .push_arg(channel),
+                )
+                .returns::<core::result::Result<(), Error>>()
+                .try_invoke();
+            match result {
+                Ok(Ok(result)) => result,
+                _ => Err(Error::HookFailed {
+                    reason: "run_hook reverted".into(),
+                }),
+            }
+        }
+
+        /// Calls the message `selector` of `contract` with the SCALE encoded `args`. The
+        /// message must return a `Result`, an `Err` fails the hook.
+        fn call_hook(&self, contract: &str, selector: [u8; 4], args: &[u8]) -> Result<(), Error> {
+            let callee = Addr::unchecked(contract)
+                .to_account_id()
+                .ok_or_else(|| Error::InvalidAddress {
+                    address: contract.into(),
+                })?;
+            let result = build_call::<crate::IBCDefaultEnvironment>()
+                .call(callee)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(selector)).push_arg(EncodedArgs(args)),
+                )
+                .returns::<core::result::Result<(), ()>>()
+                .try_invoke();
+            match result {
+                Ok(Ok(Ok(()))) => Ok(()),
+                Ok(Ok(Err(()))) => Err(Error::HookFailed {
+                    reason: "the hook returned an error".into(),
+                }),
+                _ => Err(Error::HookFailed {
+                    reason: "the hook reverted".into(),
+                }),
+            }
+        }
+
+        /// Pays out tokens escrowed for `channel`. Native tokens and registered pallet-assets
+        /// leave the channel's escrow account through the IBC pallet, contract tokens are paid
+        /// with a `PSP22::transfer` call under the token's allowlist gas limit.
+        fn send_amount(
+            &self,
+            amount: Amount,
+            recipient: String,
+            channel: &str,
+        ) -> Result<(), Error> {
+            let gas_limit = self.check_gas_limit(&amount)?;
+            let to = Addr::unchecked(recipient.clone())
+                .to_account_id()
+                .ok_or(Error::InvalidAddress { address: recipient })?;
+            match amount {
+                Amount::Native(coin) => Ok(self.env().extension().unescrow(
+                    PortId::transfer().to_string().into(),
+                    channel.into(),
+                    coin.denom.into(),
+                    coin.amount.to_string().into(),
+                    to,
+                )?),
+                Amount::Psp22(coin) => self.psp22_transfer(&coin, to, gas_limit),
+            }
+        }
+
+        /// Puts tokens we hold back into the escrow of `channel`. Contract tokens are escrowed
+        /// in our own account, they are in place already.
+        fn escrow_amount(&self, amount: Amount, channel: &str) -> Result<(), Error> {
+            match amount {
+                Amount::Native(coin) => Ok(self.env().extension().escrow(
+                    PortId::transfer().to_string().into(),
+                    channel.into(),
+                    coin.denom.into(),
+                    coin.amount.to_string().into(),
+                    self.env().account_id(),
+                )?),
+                Amount::Psp22(_) => Ok(()),
+            }
+        }
+
+        /// Mints vouchers of the local `denom` to `recipient` through the IBC pallet.
+        fn mint_vouchers(&self, denom: &str, amount: u128, recipient: String) -> Result<(), Error> {
+            let to = Addr::unchecked(recipient.clone())
+                .to_account_id()
+                .ok_or(Error::InvalidAddress { address: recipient })?;
+            Ok(self.env().extension().mint(
+                self.full_denom_path(denom)?.into(),
+                amount.to_string().into(),
+                to,
+            )?)
+        }
+
+        /// Burns vouchers of the local `denom` we hold through the IBC pallet.
+        fn burn_vouchers(&self, denom: &str, amount: u128) -> Result<(), Error> {
+            Ok(self.env().extension().burn(
+                self.full_denom_path(denom)?.into(),
+                amount.to_string().into(),
+                self.env().account_id(),
+            )?)
+        }
+
+        /// Pays `coin` out of our escrow to `to`.
+        fn psp22_transfer(
+            &self,
+            coin: &Psp22Coin,
+            to: AccountId,
+            gas_limit: Option<u64>,
+        ) -> Result<(), Error> {
+            let token = Addr::unchecked(coin.address.clone())
+                .to_account_id()
+                .ok_or_else(|| Error::InvalidAddress {
+                    address: coin.address.clone(),
+                })?;
+            let result = build_call::<crate::IBCDefaultEnvironment>()
+                .call(token)
+                // zero forwards all remaining gas
+                .gas_limit(gas_limit.unwrap_or_default())
+                .exec_input(
+                    ExecutionInput::new(Selector::new(PSP22_TRANSFER))
+                        .push_arg(to)
+                        .push_arg(coin.amount)
+                        .push_arg(Vec::<u8>::new()),
+                )
+                .returns::<core::result::Result<(), PSP22Error>>()
+                .try_invoke();
+            match result {
+                Ok(Ok(Ok(()))) => Ok(()),
+                Ok(Ok(Err(err))) => Err(Error::SendFailed {
+                    reason: format!("{:?}", err),
+                }),
+                _ => Err(Error::SendFailed {
+                    reason: "PSP22 transfer reverted".into(),
+                }),
+            }
+        }
+
+        /// Moves `coin` from `owner` into our escrow, the caller must have approved us beforehand.
+        fn psp22_transfer_from(
+            &self,
+            coin: &Psp22Coin,
+            owner: AccountId,
+            gas_limit: Option<u64>,
+        ) -> Result<(), Error> {
+            let token = Addr::unchecked(coin.address.clone())
+                .to_account_id()
+                .ok_or_else(|| Error::InvalidAddress {
+                    address: coin.address.clone(),
+                })?;
+            let result = build_call::<crate::IBCDefaultEnvironment>()
+                .call(token)
+                // zero forwards all remaining gas
+                .gas_limit(gas_limit.unwrap_or_default())
+                .exec_input(
+                    ExecutionInput::new(Selector::new(PSP22_TRANSFER_FROM))
+                        .push_arg(owner)
+                        .push_arg(self.env().account_id())
+                        .push_arg(coin.amount)
+                        .push_arg(Vec::<u8>::new()),
+                )
+                .returns::<core::result::Result<(), PSP22Error>>()
+                .try_invoke();
+            match result {
+                Ok(Ok(Ok(()))) => Ok(()),
+                Ok(Ok(Err(err))) => Err(Error::SendFailed {
+                    reason: format!("{:?}", err),
+                }),
+                _ => Err(Error::SendFailed {
+                    reason: "PSP22 transfer_from reverted".into(),
+                }),
+            }
+        }
+
+        /// Tokens escrowed for `channel` when we send them out.
+        fn increase_channel_balance(
+            &mut self,
+            channel: &str,
+            denom: &str,
+            amount: u128,
+        ) -> Result<(), Error> {
+            let key = (channel.to_string(), denom.to_string());
+            let mut state = match self.channel_state.get(&key) {
+                Some(state) => state,
+                None => {
+                    // first time this denom goes out over the channel
+                    let mut denoms = self.channel_token_denom.get(&key.0).unwrap_or_default();
+                    denoms.push(key.1.clone());
+                    self.channel_token_denom.insert(&key.0, &denoms);
+                    ChannelState::default()
+                }
+            };
+            state.outstanding = state
+                .outstanding
+                .checked_add(amount)
+                .ok_or(Error::AmountOverflow {})?;
+            state.total_sent = state
+                .total_sent
+                .checked_add(amount)
+                .ok_or(Error::AmountOverflow {})?;
+            self.channel_state.insert(&key, &state);
+            Ok(())
+        }
+
+        /// Tokens coming home over `channel`; more than was sent out can never return.
+        fn reduce_channel_balance(
+            &mut self,
+            channel: &str,
+            denom: &str,
+            amount: u128,
+        ) -> Result<(), Error> {
+            let key = (channel.to_string(), denom.to_string());
+            let mut state = self
+                .channel_state
+                .get(&key)
+                .ok_or(Error::InsufficientFunds {})?;
+            state.outstanding = state
+                .outstanding
+                .checked_sub(amount)
+                .ok_or(Error::InsufficientFunds {})?;
+            self.channel_state.insert(&key, &state);
             Ok(())
         }
+
+        /// Reverts `reduce_channel_balance` when the unescrow failed.
+        fn undo_reduce_channel_balance(
+            &mut self,
+            channel: &str,
+            denom: &str,
+            amount: u128,
+        ) -> Result<(), trait_ibc::ibc::Error> {
+            let key = (channel.to_string(), denom.to_string());
+            let mut state = self
+                .channel_state
+                .get(&key)
+                .ok_or(trait_ibc::ibc::Error::UndoReduceChannelBalanceError)?;
+            state.outstanding = state
+                .outstanding
+                .checked_add(amount)
+                .ok_or(trait_ibc::ibc::Error::UndoReduceChannelBalanceError)?;
+            self.channel_state.insert(&key, &state);
+            Ok(())
+        }
+
+        /// Reverts `increase_channel_balance` when the tokens are refunded to the sender.
+        fn undo_increase_channel_balance(
+            &mut self,
+            channel: &str,
+            denom: &str,
+            amount: u128,
+        ) -> Result<(), trait_ibc::ibc::Error> {
+            let key = (channel.to_string(), denom.to_string());
+            let mut state = self
+                .channel_state
+                .get(&key)
+                .ok_or(trait_ibc::ibc::Error::PacketAckError)?;
+            state.outstanding = state
+                .outstanding
+                .checked_sub(amount)
+                .ok_or(trait_ibc::ibc::Error::PacketAckError)?;
+            state.total_sent = state
+                .total_sent
+                .checked_sub(amount)
+                .ok_or(trait_ibc::ibc::Error::PacketAckError)?;
+            self.channel_state.insert(&key, &state);
+            Ok(())
+        }
+
+        /// The rate limit of the channel and denom as of now, with a fresh window if the last
+        /// one ended.
+        fn current_rate_limit(&self, channel: &str, denom: &str) -> Option<RateLimit> {
+            let mut limit = self.rate_limits.get((channel.to_string(), denom.to_string()))?;
+            let now = self.env().block_timestamp();
+            if now >= limit.window_end() {
+                limit.window_start = now;
+                limit.channel_value = self.channel_value(channel, denom);
+                limit.inflow = 0;
+                limit.outflow = 0;
+            }
+            Some(limit)
+        }
+
+        /// What percentage quotas are taken of: the supply of a pallet-asset as reported by
+        /// the chain, for other denoms the tokens escrowed for the channel.
+        fn channel_value(&self, channel: &str, denom: &str) -> u128 {
+            if let Some(id) = self.denom_assets.get(denom) {
+                return self.env().extension().total_supply(Some(id)).unwrap_or_default();
+            }
+            self.channel_state
+                .get((channel.to_string(), denom.to_string()))
+                .unwrap_or_default()
+                .outstanding
+        }
+
+        /// Percentage quotas of a denom without a channel value would let nothing through:
+        /// vouchers and denoms nothing was escrowed of yet need absolute quotas.
+        fn ensure_valued(
+            quota: &Quota,
+            channel_value: u128,
+            channel: &str,
+            denom: &str,
+        ) -> Result<(), Error> {
+            let percent = [quota.inflow, quota.outflow]
+                .into_iter()
+                .any(|limit| matches!(limit, Some(QuotaLimit::Percent(_))));
+            if percent && channel_value == 0 {
+                return Err(Error::InvalidQuota {
+                    reason: format!(
+                        "{} has no value on {} to take a percentage of",
+                        denom, channel
+                    ),
+                });
+            }
+            Ok(())
+        }
+
+        /// Counts tokens crossing the channel against its quota, if there is one.
+        fn consume_flow(
+            &mut self,
+            channel: &str,
+            denom: &str,
+            amount: u128,
+            flow: Flow,
+        ) -> Result<(), Error> {
+            let mut limit = match self.current_rate_limit(channel, denom) {
+                Some(limit) => limit,
+                None => return Ok(()),
+            };
+            let left = match flow {
+                Flow::In => limit.inflow_left(),
+                Flow::Out => limit.outflow_left(),
+            };
+            if let Some(left) = left {
+                if amount > left {
+                    return Err(Error::RateLimitExceeded {
+                        channel: channel.into(),
+                        denom: denom.into(),
+                    });
+                }
+            }
+            match flow {
+                Flow::In => limit.inflow = limit.inflow.saturating_add(amount),
+                Flow::Out => limit.outflow = limit.outflow.saturating_add(amount),
+            }
+            self.rate_limits
+                .insert((channel.to_string(), denom.to_string()), &limit);
+            Ok(())
+        }
+
+        /// Reverts `consume_flow` for tokens that did not cross after all. Flows of a window
+        /// that has ended are gone already.
+        fn undo_flow(&mut self, channel: &str, denom: &str, amount: u128, flow: Flow) {
+            let key = (channel.to_string(), denom.to_string());
+            let mut limit = match self.rate_limits.get(&key) {
+                Some(limit) => limit,
+                None => return,
+            };
+            if self.env().block_timestamp() >= limit.window_end() {
+                return;
+            }
+            match flow {
+                Flow::In => limit.inflow = limit.inflow.saturating_sub(amount),
+                Flow::Out => limit.outflow = limit.outflow.saturating_sub(amount),
+            }
+            self.rate_limits.insert(&key, &limit);
+        }
+
+        /// Absolute ICS-04 timeout for a packet sent now, `timeout` seconds from the current
+        /// block time, falling back to `Config.default_timeout`.
+        pub fn timeout_timestamp(&self, timeout: Option<u64>) -> Result<IbcTimestamp, Error> {
+            let seconds = timeout.unwrap_or(self.config.default_timeout);
+            if seconds == 0 {
+                return Err(Error::InvalidTimeout {});
+            }
+            IbcTimestamp::from_millis(self.env().block_timestamp())
+                .and_then(|now| now.plus_seconds(seconds))
+                .ok_or(Error::InvalidTimeout {})
+        }
+
+        /// Maps the denom of an incoming ICS-20 packet to the local denom, following ibc-go:
+        /// a token coming home has the hop unwound, anything else becomes a voucher prefixed
+        /// with our end of the channel and its trace is recorded.
+        pub fn receive_denom(&mut self, packet: &IbcPacket, denom: &str) -> Result<String, Error> {
+            if receiver_chain_is_source(&packet.src.port_id, &packet.src.channel_id, denom) {
+                let unprefixed = parse_voucher_denom(denom, &packet.src)?;
+                return Ok(DenomTrace::parse(unprefixed).ibc_denom());
+            }
+            let trace = DenomTrace::parse(denom)
+                .add_prefix(&packet.dest.port_id, &packet.dest.channel_id);
+            Ok(self.denom_traces.insert(&trace))
+        }
+
+        /// The denom to put into an outgoing packet for a local denom.
+        pub fn full_denom_path(&self, denom: &str) -> Result<String, Error> {
+            self.denom_traces
+                .resolve(denom)
+                .map(|trace| trace.full_path())
+                .ok_or_else(|| Error::DenomTraceNotFound {
+                    denom: denom.into(),
+                })
+        }
+
+        /// This must be called by gov_contract, will allow a new PSP22 token to be sent.
+        /// A contract already on the list can only get a higher gas limit.
+        #[ink(message)]
+        pub fn execute_allow(&mut self, allow: AllowMsg) -> Result<Response, Error> {
+            if Addr::from(self.env().caller()) != self.config.gov_contract {
+                return Err(Error::Unauthorized);
+            }
+            let gas_limit = allow.gas_limit;
+            let contract = self.add_allowed(allow)?;
+            self.env().emit_event(ContractAllowed {
+                contract: contract.clone(),
+                gas_limit,
+            });
+
+            let gas = match gas_limit {
+                Some(gas) => gas.to_string(),
+                None => "None".to_string(),
+            };
+            Ok(Response::new()
+                .add_attribute("action", "allow")
+                .add_attribute("contract", contract.into_string())
+                .add_attribute("gas_limit", gas))
+        }
+
+        fn add_allowed(&mut self, allow: AllowMsg) -> Result<Addr, Error> {
+            let contract = checked_addr(allow.contract)?;
+            match self.allow_list.get(&contract) {
+                Some(old) => match (old.gas_limit, allow.gas_limit) {
+                    (Some(old), Some(new)) if new < old => return Err(Error::CannotLowerGas),
+                    (Some(_), None) => return Err(Error::CannotLowerGas),
+                    _ => {}
+                },
+                None => {
+                    let mut allowed = self.allowed_contracts.get().unwrap_or_default();
+                    if let Err(pos) = allowed.binary_search(&contract) {
+                        allowed.insert(pos, contract.clone());
+                        self.allowed_contracts.set(&allowed);
+                    }
+                }
+            }
+            self.allow_list.insert(
+                &contract,
+                &AllowInfo {
+                    gas_limit: allow.gas_limit,
+                },
+            );
+            Ok(contract)
+        }
+
+        /// Contract tokens need to be on the allow list, unless there is a default gas limit
+        /// to run unknown contracts with. Returns the gas limit to use for the token.
+        fn check_gas_limit(&self, amount: &Amount) -> Result<Option<u64>, Error> {
+            match amount {
+                Amount::Psp22(coin) => {
+                    let contract = checked_addr(coin.address.clone())?;
+                    match self.allow_list.get(&contract) {
+                        Some(allow) => Ok(allow.gas_limit),
+                        None => match self.config.default_gas_limit {
+                            Some(_) => Ok(self.config.default_gas_limit),
+                            None => Err(Error::NotOnAllowList),
+                        },
+                    }
+                }
+                _ => Ok(None),
+            }
+        }
+
+        // query function list
+
+        /// The IBC base denom the pallet-asset `id` is sent as, if registered.
+        #[ink(message)]
+        pub fn query_asset_denom(&self, id: u32) -> Option<String> {
+            self.asset_denoms.get(id)
+        }
+
+        /// The pallet-asset id behind a registered IBC base denom.
+        #[ink(message)]
+        pub fn query_denom_asset(&self, denom: String) -> Option<u32> {
+            self.denom_assets.get(denom)
+        }
+
+        /// Query if a given PSP22 contract is allowed.
+        #[ink(message)]
+        pub fn query_allowed(&self, contract: String) -> AllowedResponse {
+            let allow = match checked_addr(contract) {
+                Ok(contract) => self.allow_list.get(contract),
+                Err(_) => None,
+            };
+            match allow {
+                Some(allow) => AllowedResponse {
+                    is_allowed: true,
+                    gas_limit: allow.gas_limit,
+                },
+                None => AllowedResponse {
+                    is_allowed: false,
+                    gas_limit: None,
+                },
+            }
+        }
+
+        /// List all allowed PSP22 contracts, in address order starting after `start_after`.
+        #[ink(message)]
+        pub fn query_list_allowed(
+            &self,
+            start_after: Option<String>,
+            limit: Option<u32>,
+        ) -> ListAllowedResponse {
+            let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+            let allowed = self.allowed_contracts.get().unwrap_or_default();
+            let start = match start_after {
+                Some(after) => {
+                    // a malformed cursor sorts as given, it just matches no entry
+                    let after = checked_addr(after.clone()).unwrap_or(Addr::unchecked(after));
+                    match allowed.binary_search(&after) {
+                        Ok(pos) => pos + 1,
+                        Err(pos) => pos,
+                    }
+                }
+                None => 0,
+            };
+            let allow = allowed[start..]
+                .iter()
+                .take(limit)
+                .map(|contract| AllowedInfo {
+                    contract: contract.as_str().to_string(),
+                    gas_limit: self.allow_list.get(contract).and_then(|allow| allow.gas_limit),
+                })
+                .collect();
+            ListAllowedResponse { allow }
+        }
+
+        /// Returns the details of the channel and the tokens locked for it, per denom.
+        #[ink(message)]
+        pub fn query_channel(&self, id: String) -> Result<ChannelResponse, Error> {
+            let info = self
+                .channel_info
+                .get(&id)
+                .ok_or(Error::NoSuchChannel { id: id.clone() })?;
+            let denoms = self.channel_token_denom.get(&id).unwrap_or_default();
+            let (balances, total_sent) = denoms
+                .into_iter()
+                .map(|denom| {
+                    let state = self
+                        .channel_state
+                        .get((id.clone(), denom.clone()))
+                        .unwrap_or_default();
+                    (
+                        Amount::from_parts(denom.clone(), state.outstanding),
+                        Amount::from_parts(denom, state.total_sent),
+                    )
+                })
+                .unzip();
+            Ok(ChannelResponse {
+                info,
+                balances,
+                total_sent,
+            })
+        }
+
+        /// Show the Config.
+        #[ink(message)]
+        pub fn query_config(&self) -> ConfigResponse {
+            ConfigResponse {
+                default_timeout: self.config.default_timeout,
+                default_gas_limit: self.config.default_gas_limit,
+                gov_contract: self.config.gov_contract.as_str().to_string(),
+            }
+        }
+
+        /// Show the current admin.
+        #[ink(message)]
+        pub fn query_admin(&self) -> AdminResponse {
+            AdminResponse {
+                admin: self.admin.as_str().to_string(),
+            }
+        }
+
+        /// The rate limit of the local `denom` on `channel` and its usage in the current window.
+        #[ink(message)]
+        pub fn query_rate_limit(
+            &self,
+            channel: String,
+            denom: String,
+        ) -> Option<RateLimitResponse> {
+            let limit = self.current_rate_limit(&channel, &denom)?;
+            Some(RateLimitResponse {
+                window_end: limit.window_end(),
+                inflow_left: limit.inflow_left(),
+                outflow_left: limit.outflow_left(),
+                quota: limit.quota,
+                window_start: limit.window_start,
+                channel_value: limit.channel_value,
+                inflow: limit.inflow,
+                outflow: limit.outflow,
+            })
+        }
+
+        /// The next send, receive and ack sequence of a channel, for finding stuck packets.
+        #[ink(message)]
+        pub fn query_sequences(&self, channel_id: String) -> Option<ChannelSequences> {
+            self.sequences.get(&channel_id)
+        }
+
+        /// Resolves a voucher denom to its trace. Accepts `ibc/{hash}` as well as the bare hash.
+        #[ink(message)]
+        pub fn query_denom_trace(&self, hash: String) -> Result<DenomTrace, Error> {
+            let denom = if hash.starts_with(IBC_DENOM_PREFIX) {
+                hash
+            } else {
+                IBC_DENOM_PREFIX.to_owned() + hash.as_str()
+            };
+            parse_ibc_denom_hash(&denom)
+                .and_then(|hash| self.denom_traces.get(&hash))
+                .ok_or(Error::DenomTraceNotFound { denom })
+        }
+
+        /// Return the port ID bound by this contract.
+        #[ink(message)]
+        pub fn query_port(&self) -> Result<PortResponse, Error> {
+            Ok(PortResponse {
+                port_id: PortId::transfer().to_string(),
+            })
+        }
+
+        // IBC state queries, answered by the IBC pallet
+
+        /// Every channel end known to the chain, on any port.
+        #[ink(message)]
+        pub fn query_ibc_channels(&self) -> Result<Vec<IbcChannelEnd>, Error> {
+            Ok(self.env().extension().channels()?)
+        }
+
+        /// The transfer channel end `id`: its state, connection and counterparty.
+        #[ink(message)]
+        pub fn query_ibc_channel(&self, id: String) -> Result<IbcChannelEnd, Error> {
+            let port_id = PortId::transfer().to_string();
+            self.env()
+                .extension()
+                .channel(port_id.into(), id.clone().into())?
+                .ok_or(Error::NoSuchChannel { id })
+        }
+
+        /// The sequence the next packet sent over the transfer channel `id` will get.
+        #[ink(message)]
+        pub fn query_next_sequence_send(&self, id: String) -> Result<u64, Error> {
+            let port_id = PortId::transfer().to_string();
+            Ok(self
+                .env()
+                .extension()
+                .next_sequence_send(port_id.into(), id.into())?)
+        }
+
+        /// Resolves an `ibc/{hash}` voucher denom with the traces of the chain's transfer module,
+        /// which also knows vouchers that never passed through this contract.
+        #[ink(message)]
+        pub fn query_ibc_denom_trace(&self, denom: String) -> Result<DenomTrace, Error> {
+            let trace = match parse_ibc_denom_hash(&denom) {
+                Some(hash) => self.env().extension().denom_trace(hash)?,
+                None => None,
+            };
+            trace
+                .map(|trace| DenomTrace {
+                    path: trace.path,
+                    base_denom: trace.base_denom,
+                })
+                .ok_or(Error::DenomTraceNotFound { denom })
+        }
+
+        /// The account native tokens sent over the transfer channel `id` are escrowed in.
+        #[ink(message)]
+        pub fn query_escrow_address(&self, id: String) -> Result<AccountId, Error> {
+            let port_id = PortId::transfer().to_string();
+            Ok(self
+                .env()
+                .extension()
+                .escrow_address(port_id.into(), id.into())?)
+        }
+
+        /// Refuses to send over channels the IBC pallet doesn't have open, so a packet never
+        /// leaves with tokens it would only bounce back later.
+        fn ensure_channel_open(&self, id: &str) -> Result<(), Error> {
+            if !self.channel_info.contains(id) {
+                return Err(Error::NoSuchChannel { id: id.into() });
+            }
+            let channel = self.query_ibc_channel(id.into())?;
+            if channel.state != IbcChannelState::Open {
+                return Err(Error::ChannelNotOpen { id: id.into() });
+            }
+            Ok(())
+        }
+
+        // PSP37 interface queries
+
+        /// Returns the account balance for the specified asset & owner.
+        #[ink(message)]
+        pub fn balance_of(&self, owner: AccountId, id: Option<u32>) -> Result<Balance, PSP37Error> {
+            if id.is_none() {
+                return Ok(Balance::default());
+            }
+
+            Ok(self.env().extension().balance_of(owner, id)?)
+        }
+
+        /// Returns the total token supply of the specified asset.
+        #[ink(message)]
+        pub fn total_supply(&self, id: Option<u32>) -> Result<Balance, PSP37Error> {
+            if id.is_none() {
+                return Ok(Balance::default());
+            }
+
+            Ok(self.env().extension().total_supply(id)?)
+        }
+
+        /// Returns the amount which `spender` is still allowed to withdraw from `owner`
+        /// for the specified asset.
+        #[ink(message)]
+        pub fn allowance(
+            &self,
+            owner: AccountId,
+            spender: AccountId,
+            id: Option<u32>,
+        ) -> Result<Balance, PSP37Error> {
+            if id.is_none() {
+                return Ok(Balance::default());
+            }
+
+            Ok(self.env().extension().allowance(owner, spender, id)?)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::IbcDenomTrace;
+        use ibc::core::ics02_client::height::Height;
+        use ink::env::chain_extension::FromStatusCode;
+        use ink::env::DefaultEnvironment;
+        use std::cell::RefCell;
+        use std::collections::BTreeMap;
+        use std::rc::Rc;
+        use trait_ibc::testing::{account_addr, Chain, IbcSimulator, PacketOutcome};
+
+        type Sim = IbcSimulator<Contract, Contract>;
+
+        /// A transfer the pallet took from a contract, to be relayed by the test.
+        struct Sent {
+            channel: String,
+            packet: Ics20Packet,
+            timeout: IbcTimeout,
+        }
+
+        /// Tokens the pallet paid out of the escrow account of a channel.
+        #[derive(Debug, PartialEq, Eq)]
+        struct Unescrowed {
+            channel: String,
+            denom: String,
+            amount: u128,
+            receiver: AccountId,
+        }
+
+        /// Vouchers the pallet minted.
+        #[derive(Debug, PartialEq, Eq)]
+        struct Minted {
+            denom: String,
+            amount: u128,
+            receiver: AccountId,
+        }
+
+        /// The channels the simulator opens, `channel-0` on `a` and `channel-1` on `b`, and
+        /// `channel-2` and `channel-3` for tests that open a second pair.
+        const CHANNELS: [&str; 4] = ["channel-0", "channel-1", "channel-2", "channel-3"];
+
+        /// The IBC pallet behind the chain extension, shared by both chains: its channels
+        /// are open unless closed here, and transfers are kept until the test relays them.
+        #[derive(Default)]
+        struct Pallet {
+            sent: Vec<Sent>,
+            unescrowed: Vec<Unescrowed>,
+            minted: Vec<Minted>,
+            /// what accounts gained or lost, by the denom the pallet was given: vouchers go by
+            /// their full path; nobody is funded up front
+            holdings: BTreeMap<(AccountId, String), i128>,
+            sequences: BTreeMap<String, u64>,
+            closed: Vec<String>,
+            traces: BTreeMap<[u8; 32], IbcDenomTrace>,
+            /// status codes to answer functions with instead of serving them, by function id
+            failures: BTreeMap<u32, u32>,
+        }
+
+        impl Pallet {
+            /// Moves tokens between accounts, minting them without a `from` and burning them
+            /// without a `to`.
+            fn transfer(
+                &mut self,
+                from: Option<AccountId>,
+                to: Option<AccountId>,
+                denom: &str,
+                amount: u128,
+            ) {
+                let amount = amount as i128;
+                for (account, change) in [(from, -amount), (to, amount)] {
+                    if let Some(account) = account {
+                        *self.holdings.entry((account, denom.into())).or_default() += change;
+                    }
+                }
+            }
+
+            fn holding(&self, account: &AccountId, denom: &str) -> i128 {
+                let key = (*account, denom.to_string());
+                self.holdings.get(&key).copied().unwrap_or_default()
+            }
+
+            fn channel_end(&self, channel_id: &str) -> Option<IbcChannelEnd> {
+                if !CHANNELS.contains(&channel_id) {
+                    return None;
+                }
+                let state = match self.closed.iter().any(|closed| closed == channel_id) {
+                    true => IbcChannelState::Closed,
+                    false => IbcChannelState::Open,
+                };
+                Some(IbcChannelEnd {
+                    port_id: "transfer".into(),
+                    channel_id: channel_id.into(),
+                    state,
+                    connection_id: "connection-0".into(),
+                    counterparty_port_id: "transfer".into(),
+                    counterparty_channel_id: String::new(),
+                    version: ICS20_VERSION.into(),
+                })
+            }
+        }
+
+        /// The account the pallet escrows tokens sent over `channel` in.
+        fn escrow_account(channel: &str) -> AccountId {
+            let mut account = [0xe5; 32];
+            account[..channel.len()].copy_from_slice(channel.as_bytes());
+            AccountId::from(account)
+        }
+
+        /// Serves a chain extension function: decodes the input, encodes the output and
+        /// returns the status code.
+        type Call = fn(&mut Pallet, &[u8], &mut Vec<u8>) -> u32;
+
+        struct Handler {
+            id: u32,
+            pallet: Rc<RefCell<Pallet>>,
+            call: Call,
+        }
+
+        impl ink::env::test::ChainExtension for Handler {
+            fn func_id(&self) -> u32 {
+                self.id
+            }
+
+            fn call(&mut self, input: &[u8], output: &mut Vec<u8>) -> u32 {
+                // the engine hands over the encoded arguments as a byte vector
+                let input = Vec::<u8>::decode(&mut &input[..]).unwrap();
+                let mut pallet = self.pallet.borrow_mut();
+                match pallet.failures.get(&self.id) {
+                    Some(code) => *code,
+                    None => (self.call)(&mut pallet, &input, output),
+                }
+            }
+        }
+
+        fn utf8(bytes: Vec<u8>) -> String {
+            String::from_utf8(bytes).unwrap()
+        }
+
+        fn send_transfer(pallet: &mut Pallet, input: &[u8], output: &mut Vec<u8>) -> u32 {
+            type Args = (
+                Vec<u8>,
+                Vec<u8>,
+                Vec<u8>,
+                Vec<u8>,
+                Vec<u8>,
+                Option<IbcTimeoutBlock>,
+                u64,
+                Vec<u8>,
+            );
+            let (channel, denom, amount, sender, receiver, height, timestamp, memo) =
+                Args::decode(&mut &input[..]).unwrap();
+            let channel = utf8(channel);
+            if pallet.closed.contains(&channel) {
+                return IBCICS20Error::ChannelNotOpen.status_code();
+            }
+            let memo = Some(utf8(memo)).filter(|memo| !memo.is_empty());
+            let (denom, sender) = (utf8(denom), utf8(sender));
+            let amount = utf8(amount).parse().unwrap();
+            let from = Addr::unchecked(sender.clone()).to_account_id();
+            match sender_chain_is_source("transfer", &channel, &denom) {
+                true => pallet.transfer(from, Some(escrow_account(&channel)), &denom, amount),
+                false => pallet.transfer(from, None, &denom, amount),
+            }
+            let packet = Ics20Packet::new(amount, denom, sender, utf8(receiver), memo);
+            let timestamp = IbcTimestamp::from_nanos(timestamp);
+            let timeout = match height {
+                Some(block) => IbcTimeout::with_both(block, timestamp),
+                None => IbcTimeout::with_timestamp(timestamp),
+            };
+            let sequence = pallet.sequences.entry(channel.clone()).or_insert(1);
+            sequence.encode_to(output);
+            *sequence += 1;
+            pallet.sent.push(Sent {
+                channel,
+                packet,
+                timeout,
+            });
+            0
+        }
+
+        fn channels(pallet: &mut Pallet, _input: &[u8], output: &mut Vec<u8>) -> u32 {
+            let channels: Vec<_> = CHANNELS
+                .iter()
+                .filter_map(|channel_id| pallet.channel_end(channel_id))
+                .collect();
+            channels.encode_to(output);
+            0
+        }
+
+        fn channel(pallet: &mut Pallet, input: &[u8], output: &mut Vec<u8>) -> u32 {
+            let (_port_id, channel_id) = <(Vec<u8>, Vec<u8>)>::decode(&mut &input[..]).unwrap();
+            pallet.channel_end(&utf8(channel_id)).encode_to(output);
+            0
+        }
+
+        fn next_sequence_send(pallet: &mut Pallet, input: &[u8], output: &mut Vec<u8>) -> u32 {
+            let (_port_id, channel_id) = <(Vec<u8>, Vec<u8>)>::decode(&mut &input[..]).unwrap();
+            let channel_id = utf8(channel_id);
+            if pallet.channel_end(&channel_id).is_none() {
+                return IBCICS20Error::UnknownChannel.status_code();
+            }
+            pallet.sequences.get(&channel_id).unwrap_or(&1).encode_to(output);
+            0
+        }
+
+        fn denom_trace(pallet: &mut Pallet, input: &[u8], output: &mut Vec<u8>) -> u32 {
+            let hash = <[u8; 32]>::decode(&mut &input[..]).unwrap();
+            pallet.traces.get(&hash).encode_to(output);
+            0
+        }
+
+        fn escrow_address(pallet: &mut Pallet, input: &[u8], output: &mut Vec<u8>) -> u32 {
+            let (_port_id, channel_id) = <(Vec<u8>, Vec<u8>)>::decode(&mut &input[..]).unwrap();
+            let channel_id = utf8(channel_id);
+            if pallet.channel_end(&channel_id).is_none() {
+                return IBCICS20Error::UnknownChannel.status_code();
+            }
+            escrow_account(&channel_id).encode_to(output);
+            0
+        }
+
+        fn unescrow(pallet: &mut Pallet, input: &[u8], _output: &mut Vec<u8>) -> u32 {
+            type Args = (Vec<u8>, Vec<u8>, Vec<u8>, Vec<u8>, AccountId);
+            let (_port_id, channel, denom, amount, receiver) =
+                Args::decode(&mut &input[..]).unwrap();
+            let unescrowed = Unescrowed {
+                channel: utf8(channel),
+                denom: utf8(denom),
+                amount: utf8(amount).parse().unwrap(),
+                receiver,
+            };
+            let escrow = escrow_account(&unescrowed.channel);
+            let Unescrowed { denom, amount, .. } = &unescrowed;
+            pallet.transfer(Some(escrow), Some(receiver), denom, *amount);
+            pallet.unescrowed.push(unescrowed);
+            0
+        }
+
+        fn escrow(pallet: &mut Pallet, input: &[u8], _output: &mut Vec<u8>) -> u32 {
+            type Args = (Vec<u8>, Vec<u8>, Vec<u8>, Vec<u8>, AccountId);
+            let (_port_id, channel, denom, amount, owner) = Args::decode(&mut &input[..]).unwrap();
+            let escrow = escrow_account(&utf8(channel));
+            let amount = utf8(amount).parse().unwrap();
+            pallet.transfer(Some(owner), Some(escrow), &utf8(denom), amount);
+            0
+        }
+
+        fn mint(pallet: &mut Pallet, input: &[u8], _output: &mut Vec<u8>) -> u32 {
+            let (denom, amount, receiver) =
+                <(Vec<u8>, Vec<u8>, AccountId)>::decode(&mut &input[..]).unwrap();
+            let minted = Minted {
+                denom: utf8(denom),
+                amount: utf8(amount).parse().unwrap(),
+                receiver,
+            };
+            pallet.transfer(None, Some(receiver), &minted.denom, minted.amount);
+            pallet.minted.push(minted);
+            0
+        }
+
+        fn burn(pallet: &mut Pallet, input: &[u8], _output: &mut Vec<u8>) -> u32 {
+            let (denom, amount, owner) =
+                <(Vec<u8>, Vec<u8>, AccountId)>::decode(&mut &input[..]).unwrap();
+            pallet.transfer(Some(owner), None, &utf8(denom), utf8(amount).parse().unwrap());
+            0
+        }
+
+        fn register_pallet() -> Rc<RefCell<Pallet>> {
+            let pallet = Rc::new(RefCell::new(Pallet::default()));
+            let calls: [(u32, Call); 10] = [
+                (0x20002, channels),
+                (0x20003, channel),
+                (0x20004, next_sequence_send),
+                (0x20005, denom_trace),
+                (0x20006, escrow_address),
+                (0x20007, send_transfer),
+                (0x20008, unescrow),
+                (0x20009, mint),
+                (0x2000a, burn),
+                (0x2000b, escrow),
+            ];
+            for (id, call) in calls {
+                ink::env::test::register_chain_extension(Handler {
+                    id,
+                    pallet: pallet.clone(),
+                    call,
+                });
+            }
+            pallet
+        }
+
+        fn accounts() -> ink::env::test::DefaultAccounts<DefaultEnvironment> {
+            ink::env::test::default_accounts::<DefaultEnvironment>()
+        }
+
+        fn contract() -> Contract {
+            Contract::new(InitMsg {
+                default_timeout: 600,
+                gov_contract: account_addr(&accounts().charlie).into_string(),
+                allowlist: vec![],
+                default_gas_limit: None,
+            })
+            .unwrap()
+        }
+
+        /// Two ICS-20 contracts with an open channel, `channel-0` on `a` and `channel-1` on `b`.
+        fn setup() -> (Sim, Rc<RefCell<Pallet>>) {
+            let pallet = register_pallet();
+            let a = Chain::instantiate(accounts().alice, "transfer", contract);
+            let b = Chain::instantiate(accounts().bob, "transfer", contract);
+            let mut sim = IbcSimulator::new(a, b);
+            let (channel_a, channel_b) = sim.open_channel(ICS20_ORDERING, ICS20_VERSION).unwrap();
+            assert_eq!((channel_a.as_str(), channel_b.as_str()), ("channel-0", "channel-1"));
+            (sim, pallet)
+        }
+
+        fn transfer_msg(channel: &str, receiver: &AccountId) -> TransferMsg {
+            TransferMsg {
+                channel: channel.into(),
+                remote_address: account_addr(receiver).into_string(),
+                timeout: None,
+                timeout_height: TimeoutHeight::Never,
+                memo: None,
+            }
+        }
+
+        /// Makes the calls that follow as `account`, whose address is returned to send from.
+        fn act_as(account: &AccountId) -> Addr {
+            ink::env::test::set_caller::<DefaultEnvironment>(*account);
+            account_addr(account)
+        }
+
+        /// Hands the transfers the pallet took to the simulator.
+        fn flush(sim: &mut Sim, pallet: &Rc<RefCell<Pallet>>) {
+            let sent = core::mem::take(&mut pallet.borrow_mut().sent);
+            for Sent {
+                channel,
+                packet,
+                timeout,
+            } in sent
+            {
+                match sim.a.channel(&channel) {
+                    Some(_) => sim.a.send_packet(&channel, packet.to_json(), timeout),
+                    None => sim.b.send_packet(&channel, packet.to_json(), timeout),
+                };
+            }
+        }
+
+        /// Flushes the pallet and relays until quiet.
+        fn relay(sim: &mut Sim, pallet: &Rc<RefCell<Pallet>>) -> Vec<PacketOutcome> {
+            let mut outcomes = Vec::new();
+            loop {
+                flush(sim, pallet);
+                let relayed = sim.relay().unwrap();
+                if relayed.is_empty() {
+                    return outcomes;
+                }
+                outcomes.extend(relayed);
+            }
+        }
+
+        fn acknowledgement(outcome: &PacketOutcome) -> StdAck {
+            match outcome {
+                PacketOutcome::Acknowledged {
+                    acknowledgement, ..
+                } => IbcAcknowledgement::new(acknowledgement.clone()).std_ack().unwrap(),
+                outcome => panic!("unexpected {:?}", outcome),
+            }
+        }
+
+        /// What is escrowed for the channel, per denom.
+        fn balances(chain: &mut Chain<Contract>, channel: &str) -> Vec<(String, u128)> {
+            let channel = chain.call(|app| app.query_channel(channel.into())).unwrap();
+            channel
+                .balances
+                .iter()
+                .map(|amount| (amount.denom(), amount.amount()))
+                .collect()
+        }
+
+        /// Sends 100uatom from eve on `a` to frank on `b`, who receives them as vouchers.
+        fn send_uatom(sim: &mut Sim, pallet: &Rc<RefCell<Pallet>>) -> Vec<PacketOutcome> {
+            let msg = transfer_msg("channel-0", &accounts().frank);
+            let sender = act_as(&accounts().eve);
+            sim.a
+                .call(|app| app.execute_transfer(msg, Amount::native(100, "uatom"), sender))
+                .unwrap();
+            relay(sim, pallet)
+        }
+
+        #[ink::test]
+        fn sends_native_tokens_and_mints_vouchers() {
+            let (mut sim, pallet) = setup();
+            let msg = transfer_msg("channel-0", &accounts().frank);
+            let sender = act_as(&accounts().eve);
+            let res = sim
+                .a
+                .call(|app| app.execute_transfer(msg, Amount::native(100, "uatom"), sender))
+                .unwrap();
+            assert_eq!(res.data, Some(1u64.encode().into()));
+            {
+                let pallet = pallet.borrow();
+                let packet = &pallet.sent[0].packet;
+                assert_eq!((packet.denom.as_str(), packet.amount), ("uatom", 100));
+                assert_eq!(packet.sender, account_addr(&accounts().eve).into_string());
+            }
+
+            let outcomes = relay(&mut sim, &pallet);
+            assert_eq!(outcomes.len(), 1);
+            assert_eq!(acknowledgement(&outcomes[0]), ack_success());
+            assert_eq!(balances(&mut sim.a, "channel-0"), vec![("uatom".into(), 100)]);
+            assert!(pallet.borrow().unescrowed.is_empty());
+
+            // b minted vouchers and knows where they come from
+            let voucher = DenomTrace::parse("transfer/channel-1/uatom").ibc_denom();
+            let trace = sim.b.call(|app| app.query_denom_trace(voucher)).unwrap();
+            assert_eq!(trace.full_path(), "transfer/channel-1/uatom");
+            // nothing was escrowed on b, vouchers are minted
+            assert!(balances(&mut sim.b, "channel-1").is_empty());
+            assert_eq!(
+                pallet.borrow().minted,
+                vec![Minted {
+                    denom: "transfer/channel-1/uatom".into(),
+                    amount: 100,
+                    receiver: accounts().frank,
+                }]
+            );
+        }
+
+        #[ink::test]
+        fn mints_burnt_vouchers_back_on_error_ack() {
+            let (mut sim, pallet) = setup();
+            send_uatom(&mut sim, &pallet);
+            // a cannot make sense of the memo and answers with an error ack
+            send_vouchers_home(&mut sim, r#"{"ink":"nope"}"#.into());
+            let outcomes = relay(&mut sim, &pallet);
+            assert!(!acknowledgement(&outcomes[0]).is_success());
+
+            assert_eq!(
+                pallet.borrow().minted[1],
+                Minted {
+                    denom: "transfer/channel-1/uatom".into(),
+                    amount: 40,
+                    receiver: accounts().frank,
+                }
+            );
+        }
+
+        #[ink::test]
+        fn answers_failed_mints_with_an_error_ack() {
+            let (mut sim, pallet) = setup();
+            pallet.borrow_mut().failures.insert(0x20009, 14);
+            let outcomes = send_uatom(&mut sim, &pallet);
+            let err = Error::ChainExtension(IBCICS20Error::InvalidAddress);
+            assert_eq!(acknowledgement(&outcomes[0]), ack_fail(&err));
+            // the send was refunded out of escrow
+            assert_eq!(pallet.borrow().unescrowed.len(), 1);
+            assert_eq!(balances(&mut sim.a, "channel-0"), vec![("uatom".into(), 0)]);
+        }
+
+        #[ink::test]
+        fn refunds_out_of_escrow_on_error_ack() {
+            let (mut sim, pallet) = setup();
+            let mut msg = transfer_msg("channel-0", &accounts().frank);
+            // b cannot make sense of the memo and answers with an error ack
+            msg.memo = Some(r#"{"ink":"nope"}"#.into());
+            let sender = act_as(&accounts().eve);
+            sim.a
+                .call(|app| app.execute_transfer(msg, Amount::native(100, "uatom"), sender))
+                .unwrap();
+            let outcomes = relay(&mut sim, &pallet);
+            assert!(!acknowledgement(&outcomes[0]).is_success());
+
+            assert_eq!(
+                pallet.borrow().unescrowed,
+                vec![Unescrowed {
+                    channel: "channel-0".into(),
+                    denom: "uatom".into(),
+                    amount: 100,
+                    receiver: accounts().eve,
+                }]
+            );
+            assert_eq!(balances(&mut sim.a, "channel-0"), vec![("uatom".into(), 0)]);
+        }
+
+        #[ink::test]
+        fn refunds_out_of_escrow_on_timeout() {
+            let (mut sim, pallet) = setup();
+            let mut msg = transfer_msg("channel-0", &accounts().frank);
+            msg.timeout = Some(10);
+            let sender = act_as(&accounts().eve);
+            sim.a
+                .call(|app| app.execute_transfer(msg, Amount::native(100, "uatom"), sender))
+                .unwrap();
+            sim.advance(1, 20);
+            let outcomes = relay(&mut sim, &pallet);
+            assert!(matches!(outcomes[0], PacketOutcome::TimedOut { .. }));
+
+            assert_eq!(
+                pallet.borrow().unescrowed,
+                vec![Unescrowed {
+                    channel: "channel-0".into(),
+                    denom: "uatom".into(),
+                    amount: 100,
+                    receiver: accounts().eve,
+                }]
+            );
+            assert_eq!(balances(&mut sim.a, "channel-0"), vec![("uatom".into(), 0)]);
+        }
+
+        #[ink::test]
+        fn unescrows_tokens_coming_home() {
+            let (mut sim, pallet) = setup();
+            send_uatom(&mut sim, &pallet);
+
+            // frank sends some of the vouchers back to django on a
+            let voucher = DenomTrace::parse("transfer/channel-1/uatom").ibc_denom();
+            let msg = transfer_msg("channel-1", &accounts().django);
+            let sender = act_as(&accounts().frank);
+            sim.b
+                .call(|app| app.execute_transfer(msg, Amount::native(40, &voucher), sender))
+                .unwrap();
+            assert_eq!(pallet.borrow().sent[0].packet.denom, "transfer/channel-1/uatom");
+            let outcomes = relay(&mut sim, &pallet);
+            assert_eq!(acknowledgement(&outcomes[0]), ack_success());
+
+            assert_eq!(
+                pallet.borrow().unescrowed,
+                vec![Unescrowed {
+                    channel: "channel-0".into(),
+                    denom: "uatom".into(),
+                    amount: 40,
+                    receiver: accounts().django,
+                }]
+            );
+            assert_eq!(balances(&mut sim.a, "channel-0"), vec![("uatom".into(), 60)]);
+        }
+
+        #[ink::test]
+        fn channel_query_tracks_escrow_and_total_sent() {
+            let (mut sim, pallet) = setup();
+            send_uatom(&mut sim, &pallet);
+            // a refunded send is undone completely
+            let mut msg = transfer_msg("channel-0", &accounts().frank);
+            msg.timeout = Some(10);
+            let sender = act_as(&accounts().eve);
+            sim.a
+                .call(|app| app.execute_transfer(msg, Amount::native(50, "uatom"), sender))
+                .unwrap();
+            sim.advance(1, 20);
+            relay(&mut sim, &pallet);
+            // tokens coming home leave the channel, but still count as sent
+            let voucher = DenomTrace::parse("transfer/channel-1/uatom").ibc_denom();
+            let msg = transfer_msg("channel-1", &accounts().django);
+            let sender = act_as(&accounts().frank);
+            sim.b
+                .call(|app| app.execute_transfer(msg, Amount::native(40, &voucher), sender))
+                .unwrap();
+            relay(&mut sim, &pallet);
+
+            let channel = sim.a.call(|app| app.query_channel("channel-0".into())).unwrap();
+            assert_eq!(channel.info.id, "channel-0");
+            assert_eq!(channel.info.counterparty_endpoint.channel_id, "channel-1");
+            let amounts = |amounts: &[Amount]| -> Vec<(String, u128)> {
+                amounts.iter().map(|amount| (amount.denom(), amount.amount())).collect()
+            };
+            assert_eq!(amounts(&channel.balances), vec![("uatom".into(), 60)]);
+            assert_eq!(amounts(&channel.total_sent), vec![("uatom".into(), 100)]);
+
+            let unknown = sim.a.call(|app| app.query_channel("channel-7".into()));
+            assert!(matches!(unknown, Err(Error::NoSuchChannel { id }) if id == "channel-7"));
+        }
+
+        /// Calls `f` on the chain as the governance account.
+        fn as_gov<R>(chain: &mut Chain<Contract>, f: impl FnOnce(&mut Contract) -> R) -> R {
+            ink::env::test::set_caller::<DefaultEnvironment>(accounts().charlie);
+            let result = chain.call(f);
+            ink::env::test::set_caller::<DefaultEnvironment>(accounts().alice);
+            result
+        }
+
+        fn allow(contract: &str, gas_limit: Option<u64>) -> AllowMsg {
+            AllowMsg {
+                contract: contract.into(),
+                gas_limit,
+            }
+        }
+
+        #[ink::test]
+        fn gov_allows_contracts_by_normalised_address() {
+            let (mut sim, _pallet) = setup();
+            let token = account_addr(&AccountId::from([0xab; 32])).into_string();
+            let shouting = format!("0x{}", token[2..].to_uppercase());
+
+            let res = sim.a.call(|app| app.execute_allow(allow(&token, Some(5))));
+            assert_eq!(res.err(), Some(Error::Unauthorized));
+            as_gov(&mut sim.a, |app| app.execute_allow(allow(&shouting, Some(5)))).unwrap();
+            // the same contract in another case is no new entry and cannot lower the gas
+            let res = as_gov(&mut sim.a, |app| app.execute_allow(allow(&token, Some(4))));
+            assert_eq!(res.err(), Some(Error::CannotLowerGas));
+            let res = as_gov(&mut sim.a, |app| app.execute_allow(allow(&token, None)));
+            assert_eq!(res.err(), Some(Error::CannotLowerGas));
+            as_gov(&mut sim.a, |app| app.execute_allow(allow(&token, Some(7)))).unwrap();
+            let res = as_gov(&mut sim.a, |app| app.execute_allow(allow("0xab", None)));
+            assert!(matches!(res, Err(Error::InvalidAddress { .. })));
+
+            let allowed = sim.a.call(|app| app.query_allowed(shouting.clone()));
+            assert!(allowed.is_allowed);
+            assert_eq!(allowed.gas_limit, Some(7));
+            let listed = sim.a.call(|app| app.query_list_allowed(None, None));
+            let listed: Vec<_> = listed.allow.iter().map(|info| info.contract.clone()).collect();
+            assert_eq!(listed, vec![token]);
+        }
+
+        #[ink::test]
+        fn lists_allowed_contracts_in_pages() {
+            let (mut sim, _pallet) = setup();
+            let tokens: Vec<String> = [3u8, 1, 2]
+                .iter()
+                .map(|byte| account_addr(&AccountId::from([*byte; 32])).into_string())
+                .collect();
+            for token in &tokens {
+                as_gov(&mut sim.a, |app| app.execute_allow(allow(token, None))).unwrap();
+            }
+
+            let page = |sim: &mut Sim, after: Option<&String>| -> Vec<String> {
+                let after = after.cloned();
+                let listed = sim.a.call(|app| app.query_list_allowed(after, Some(2)));
+                listed.allow.into_iter().map(|info| info.contract).collect()
+            };
+            let first = page(&mut sim, None);
+            assert_eq!(first, vec![tokens[1].clone(), tokens[2].clone()]);
+            let second = page(&mut sim, first.last());
+            assert_eq!(second, vec![tokens[0].clone()]);
+            assert!(page(&mut sim, second.last()).is_empty());
+        }
+
+        #[ink::test]
+        fn constructor_validates_and_seeds_config() {
+            let gov = account_addr(&accounts().charlie).into_string();
+            let token = account_addr(&AccountId::from([0xab; 32])).into_string();
+            let init = |default_timeout, gov_contract: &str| InitMsg {
+                default_timeout,
+                gov_contract: gov_contract.into(),
+                allowlist: vec![allow(&token, Some(9))],
+                default_gas_limit: Some(3),
+            };
+            assert_eq!(Contract::new(init(0, &gov)).err(), Some(Error::InvalidTimeout {}));
+            let res = Contract::new(init(600, "charlie"));
+            assert!(matches!(res, Err(Error::InvalidAddress { .. })));
+
+            let contract = Contract::new(init(600, &gov)).unwrap();
+            let config = contract.query_config();
+            assert_eq!(config.default_timeout, 600);
+            assert_eq!(config.default_gas_limit, Some(3));
+            assert_eq!(config.gov_contract, gov);
+            let admin = account_addr(&accounts().alice).into_string();
+            assert_eq!(contract.query_admin().admin, admin);
+            assert_eq!(contract.query_allowed(token).gas_limit, Some(9));
+        }
+
+        #[ink::test]
+        fn admin_rotates_and_updates_config() {
+            let (mut sim, pallet) = setup();
+            let bob = account_addr(&accounts().bob).into_string();
+            let gov = account_addr(&accounts().django).into_string();
+
+            sim.a.call(|app| app.update_admin(bob.clone())).unwrap();
+            assert_eq!(sim.a.call(|app| app.query_admin()).admin, bob);
+            // alice is no admin anymore
+            let res = sim.a.call(|app| app.update_config(30, None, gov.clone()));
+            assert_eq!(res, Err(Error::AdminError));
+            ink::env::test::set_caller::<DefaultEnvironment>(accounts().bob);
+            let res = sim.a.call(|app| app.update_config(0, None, gov.clone()));
+            assert_eq!(res, Err(Error::InvalidTimeout {}));
+            sim.a.call(|app| app.update_config(30, Some(5), gov.clone())).unwrap();
+            ink::env::test::set_caller::<DefaultEnvironment>(accounts().alice);
+            let config = sim.a.call(|app| app.query_config());
+            assert_eq!((config.default_timeout, config.default_gas_limit), (30, Some(5)));
+            assert_eq!(config.gov_contract, gov);
+
+            // sends without a timeout of their own use the new default
+            let msg = transfer_msg("channel-0", &accounts().frank);
+            let sender = act_as(&accounts().eve);
+            sim.a
+                .call(|app| app.execute_transfer(msg, Amount::native(100, "uatom"), sender))
+                .unwrap();
+            let timeout = pallet.borrow().sent[0].timeout.timestamp();
+            assert_eq!(timeout, sim.a.time.plus_seconds(30));
+        }
+
+        #[ink::test]
+        fn psp22_denoms_round_trip() {
+            let token = account_addr(&AccountId::from([0xab; 32])).into_string();
+            let denom = format!("psp22:{}", token);
+            let amount = Amount::from_parts(denom.clone(), 5);
+            assert!(matches!(&amount, Amount::Psp22(coin) if coin.address == token));
+            assert_eq!((amount.denom(), amount.amount()), (denom, 5));
+            let amount = Amount::from_parts("uatom".into(), 5);
+            assert!(matches!(amount, Amount::Native(coin) if coin.denom == "uatom"));
+        }
+
+        #[ink::test]
+        fn psp22_sends_need_the_allowlist() {
+            let (mut sim, pallet) = setup();
+            let token = account_addr(&AccountId::from([0xab; 32])).into_string();
+            let msg = transfer_msg("channel-0", &accounts().frank);
+            let sender = act_as(&accounts().eve);
+            let res = sim
+                .a
+                .call(|app| app.execute_transfer(msg, Amount::psp22(100, &token), sender));
+            assert_eq!(res.err(), Some(Error::NotOnAllowList));
+            assert!(pallet.borrow().sent.is_empty());
+        }
+
+        #[ink::test]
+        fn psp22_tokens_arrive_as_vouchers() {
+            let (mut sim, pallet) = setup();
+            let token = account_addr(&AccountId::from([0xab; 32])).into_string();
+            let denom = format!("psp22:{}", token);
+            // a escrowed the contract tokens, the pallet sends them under their denom
+            let sender = account_addr(&accounts().eve).into_string();
+            let receiver = account_addr(&accounts().frank).into_string();
+            let packet = Ics20Packet::new(100, denom.clone(), sender, receiver, None);
+            let timeout = IbcTimeout::with_timestamp(sim.a.time.plus_seconds(60).unwrap());
+            sim.a.send_packet("channel-0", packet.to_json(), timeout);
+            let outcomes = relay(&mut sim, &pallet);
+            assert_eq!(acknowledgement(&outcomes[0]), ack_success());
+
+            let path = format!("transfer/channel-1/{}", denom);
+            let voucher = DenomTrace::parse(&path).ibc_denom();
+            let trace = sim.b.call(|app| app.query_denom_trace(voucher)).unwrap();
+            assert_eq!(trace.full_path(), path);
+        }
+
+        #[ink::test]
+        fn gov_registers_assets_once() {
+            let (mut sim, _pallet) = setup();
+            let res = sim.a.call(|app| app.register_asset(7, "dot".into()));
+            assert_eq!(res.err(), Some(Error::Unauthorized));
+            for denom in ["", "transfer/channel-0/dot", "psp22:0xab"] {
+                let res = as_gov(&mut sim.a, |app| app.register_asset(7, denom.into()));
+                assert_eq!(res.err(), Some(Error::InvalidDenom { denom: denom.into() }));
+            }
+            as_gov(&mut sim.a, |app| app.register_asset(7, "dot".into())).unwrap();
+            let res = as_gov(&mut sim.a, |app| app.register_asset(7, "ksm".into()));
+            assert_eq!(res.err(), Some(Error::AssetAlreadyRegistered { id: 7 }));
+            let res = as_gov(&mut sim.a, |app| app.register_asset(8, "dot".into()));
+            assert_eq!(res.err(), Some(Error::DenomAlreadyRegistered { denom: "dot".into() }));
+
+            assert_eq!(sim.a.call(|app| app.query_asset_denom(7)), Some("dot".into()));
+            assert_eq!(sim.a.call(|app| app.query_denom_asset("dot".into())), Some(7));
+            assert_eq!(sim.a.call(|app| app.query_asset_denom(8)), None);
+        }
+
+        #[ink::test]
+        fn sends_assets_from_the_caller_and_refunds_them() {
+            let (mut sim, pallet) = setup();
+            let frank = account_addr(&accounts().frank).into_string();
+            let res = sim
+                .a
+                .call(|app| app.transfer_asset(7, 100, "channel-0".into(), frank.clone()));
+            assert_eq!(res.err(), Some(Error::UnknownAsset { id: 7 }));
+            as_gov(&mut sim.a, |app| app.register_asset(7, "dot".into())).unwrap();
+
+            let eve = act_as(&accounts().eve);
+            sim.a
+                .call(|app| app.transfer_asset(7, 100, "channel-0".into(), frank))
+                .unwrap();
+            // the pallet escrows from the caller, nobody else's tokens can be sent
+            let msg = transfer_msg("channel-0", &accounts().frank);
+            let django = account_addr(&accounts().django);
+            let res = sim
+                .a
+                .call(|app| app.execute_transfer(msg, Amount::native(50, "dot"), django.clone()));
+            let err = Error::InvalidSender {
+                sender: django.into_string(),
+            };
+            assert_eq!(res.err(), Some(err));
+            let msg = transfer_msg("channel-0", &accounts().frank);
+            sim.a
+                .call(|app| app.execute_transfer(msg, Amount::native(50, "dot"), eve))
+                .unwrap();
+            ink::env::test::set_caller::<DefaultEnvironment>(accounts().alice);
+            let eve = account_addr(&accounts().eve).into_string();
+            for sent in &pallet.borrow().sent {
+                assert_eq!((sent.packet.denom.as_str(), &sent.packet.sender), ("dot", &eve));
+            }
+            assert_eq!(balances(&mut sim.a, "channel-0"), vec![("dot".into(), 150)]);
+
+            sim.advance(1, 700);
+            relay(&mut sim, &pallet);
+            let refunded: Vec<_> = pallet
+                .borrow()
+                .unescrowed
+                .iter()
+                .map(|refund| (refund.denom.clone(), refund.amount, refund.receiver))
+                .collect();
+            let eve = accounts().eve;
+            assert_eq!(refunded, vec![("dot".into(), 100, eve), ("dot".into(), 50, eve)]);
+        }
+
+        #[ink::test]
+        fn queries_ibc_state_from_the_pallet() {
+            let (mut sim, pallet) = setup();
+            let channels = sim.a.call(|app| app.query_ibc_channels()).unwrap();
+            let ids: Vec<_> = channels.iter().map(|end| end.channel_id.as_str()).collect();
+            assert_eq!(ids, CHANNELS);
+            let channel = sim.a.call(|app| app.query_ibc_channel("channel-0".into())).unwrap();
+            assert_eq!(channel.state, IbcChannelState::Open);
+            let unknown = sim.a.call(|app| app.query_ibc_channel("channel-7".into()));
+            assert_eq!(unknown, Err(Error::NoSuchChannel { id: "channel-7".into() }));
+
+            let next = |sim: &mut Sim| {
+                sim.a.call(|app| app.query_next_sequence_send("channel-0".into()))
+            };
+            assert_eq!(next(&mut sim), Ok(1));
+            send_uatom(&mut sim, &pallet);
+            assert_eq!(next(&mut sim), Ok(2));
+            let unknown = sim.a.call(|app| app.query_next_sequence_send("channel-7".into()));
+            assert_eq!(unknown, Err(Error::ChainExtension(IBCICS20Error::UnknownChannel)));
+
+            let escrow = sim.a.call(|app| app.query_escrow_address("channel-0".into()));
+            assert_eq!(escrow, Ok(escrow_account("channel-0")));
+        }
+
+        #[ink::test]
+        fn resolves_vouchers_the_contract_never_saw() {
+            let (mut sim, pallet) = setup();
+            let voucher = DenomTrace::parse("transfer/channel-0/uosmo").ibc_denom();
+            let res = sim.a.call(|app| app.query_ibc_denom_trace(voucher.clone()));
+            assert_eq!(res, Err(Error::DenomTraceNotFound { denom: voucher.clone() }));
+            pallet.borrow_mut().traces.insert(
+                parse_ibc_denom_hash(&voucher).unwrap(),
+                IbcDenomTrace {
+                    path: "transfer/channel-0".into(),
+                    base_denom: "uosmo".into(),
+                },
+            );
+            let trace = sim.a.call(|app| app.query_ibc_denom_trace(voucher.clone())).unwrap();
+            assert_eq!(trace.full_path(), "transfer/channel-0/uosmo");
+            // the contract's own traces only know what came through it
+            assert!(sim.a.call(|app| app.query_denom_trace(voucher)).is_err());
+            let res = sim.a.call(|app| app.query_ibc_denom_trace("uatom".into()));
+            assert_eq!(res, Err(Error::DenomTraceNotFound { denom: "uatom".into() }));
+        }
+
+        #[ink::test]
+        fn status_codes_round_trip() {
+            let errors = [
+                IBCICS20Error::FailIBCCall,
+                IBCICS20Error::FailScaleCode,
+                IBCICS20Error::UnknownChannel,
+                IBCICS20Error::ChannelNotOpen,
+                IBCICS20Error::InvalidDenom,
+                IBCICS20Error::InvalidAmount,
+                IBCICS20Error::InvalidAddress,
+                IBCICS20Error::SendDisabled,
+                IBCICS20Error::ReceiveDisabled,
+                IBCICS20Error::InvalidTimeout,
+                IBCICS20Error::InsufficientBalance,
+                IBCICS20Error::InsufficientAllowance,
+                IBCICS20Error::UnknownAsset,
+                IBCICS20Error::AssetFrozen,
+                IBCICS20Error::AccountFrozen,
+                IBCICS20Error::BelowMinimum,
+                IBCICS20Error::NoPermission,
+                IBCICS20Error::Unknown(99),
+            ];
+            for err in errors {
+                assert_eq!(IBCICS20Error::from_status_code(err.status_code()), Err(err));
+            }
+            assert_eq!(IBCICS20Error::from_status_code(0), Ok(()));
+        }
+
+        #[ink::test]
+        fn passes_send_failures_through_with_their_status() {
+            let (mut sim, pallet) = setup();
+            pallet.borrow_mut().failures.insert(0x20007, 15);
+            let msg = transfer_msg("channel-0", &accounts().frank);
+            let sender = act_as(&accounts().eve);
+            let res = sim
+                .a
+                .call(|app| app.execute_transfer(msg, Amount::native(100, "uatom"), sender));
+            let err = trait_ibc::ibc::Error::SendPacketError {
+                code: 15,
+                reason: "Sending transfers is disabled".into(),
+            };
+            assert_eq!(res.err(), Some(Error::IBCError(err)));
+            // nothing was escrowed for the failed send
+            assert!(balances(&mut sim.a, "channel-0").is_empty());
+        }
+
+        #[ink::test]
+        fn answers_failed_unescrows_with_an_error_ack() {
+            let (mut sim, pallet) = setup();
+            send_uatom(&mut sim, &pallet);
+            pallet.borrow_mut().failures.insert(0x20008, 23);
+
+            let voucher = DenomTrace::parse("transfer/channel-1/uatom").ibc_denom();
+            let msg = transfer_msg("channel-1", &accounts().django);
+            let sender = act_as(&accounts().frank);
+            sim.b
+                .call(|app| app.execute_transfer(msg, Amount::native(40, &voucher), sender))
+                .unwrap();
+            let outcomes = relay(&mut sim, &pallet);
+            assert!(!acknowledgement(&outcomes[0]).is_success());
+            // the tokens stay escrowed for the channel
+            assert_eq!(balances(&mut sim.a, "channel-0"), vec![("uatom".into(), 100)]);
+        }
+
+        #[ink::test]
+        fn passes_memo_and_timeouts_to_the_pallet() {
+            let (mut sim, pallet) = setup();
+            let mut msg = transfer_msg("channel-0", &accounts().frank);
+            msg.timeout = Some(3600);
+            msg.timeout_height = TimeoutHeight::At(Height::new(1, 50).unwrap());
+            msg.memo = Some("thanks for all the fish".into());
+            let eve = act_as(&accounts().eve);
+            let sender = Addr::unchecked(format!("0x{}", eve.as_str()[2..].to_uppercase()));
+            let res = sim
+                .a
+                .call(|app| app.execute_transfer(msg, Amount::native(100, "uatom"), sender.clone()))
+                .unwrap();
+            assert_eq!(res.data, Some(1u64.encode().into()));
+            {
+                let pallet = pallet.borrow();
+                let sent = &pallet.sent[0];
+                assert_eq!(sent.packet.memo.as_deref(), Some("thanks for all the fish"));
+                // the pallet gets the caller's own address, however the sender was spelled
+                assert_eq!(sent.packet.sender, eve.as_str());
+                assert_eq!(sent.timeout.block(), Some(IbcTimeoutBlock::new(1, 50)));
+                assert_eq!(sent.timeout.timestamp(), sim.a.time.plus_seconds(3600));
+            }
+            let msg = transfer_msg("channel-0", &accounts().frank);
+            let res = sim
+                .a
+                .call(|app| app.execute_transfer(msg, Amount::native(100, "uatom"), sender))
+                .unwrap();
+            assert_eq!(res.data, Some(2u64.encode().into()));
+            assert_eq!(pallet.borrow().sent[1].timeout.block(), None);
+
+            // the first send times out by height and is refunded
+            sim.advance(60, 1);
+            let outcomes = relay(&mut sim, &pallet);
+            assert!(matches!(outcomes[0], PacketOutcome::TimedOut { .. }));
+            assert_eq!(acknowledgement(&outcomes[1]), ack_success());
+            assert_eq!(pallet.borrow().unescrowed.len(), 1);
+            assert_eq!(balances(&mut sim.a, "channel-0"), vec![("uatom".into(), 100)]);
+        }
+
+        #[ink::test]
+        fn refuses_sends_before_they_reach_the_pallet() {
+            let (mut sim, pallet) = setup();
+            let mut send = |msg: TransferMsg, amount: u128| {
+                let sender = act_as(&accounts().eve);
+                let amount = Amount::native(amount, "uatom");
+                sim.a.call(|app| app.execute_transfer(msg, amount, sender)).err()
+            };
+            let frank = accounts().frank;
+
+            assert_eq!(send(transfer_msg("channel-0", &frank), 0), Some(Error::NoFunds {}));
+            let mut msg = transfer_msg("channel-0", &frank);
+            msg.timeout = Some(0);
+            assert_eq!(send(msg, 100), Some(Error::InvalidTimeout {}));
+            let id = "channel-7".to_string();
+            let res = send(transfer_msg(&id, &frank), 100);
+            assert_eq!(res, Some(Error::NoSuchChannel { id }));
+            pallet.borrow_mut().closed.push("channel-0".into());
+            let id = "channel-0".to_string();
+            let res = send(transfer_msg(&id, &frank), 100);
+            assert_eq!(res, Some(Error::ChannelNotOpen { id }));
+            let msg = transfer_msg("channel-0", &frank);
+            let sender = account_addr(&frank);
+            let amount = Amount::native(100, "uatom");
+            let res = sim.a.call(|app| app.execute_transfer(msg, amount, sender.clone()));
+            let err = Error::InvalidSender {
+                sender: sender.into_string(),
+            };
+            assert_eq!(res.err(), Some(err));
+            assert!(pallet.borrow().sent.is_empty());
+        }
+
+        /// An `ink` memo calling `contract`.
+        fn hook_memo(contract: &AccountId) -> String {
+            let contract = account_addr(contract).into_string();
+            format!(r#"{{"ink":{{"contract":"{}","selector":"0x12345678"}}}}"#, contract)
+        }
+
+        #[ink::test]
+        fn refuses_hooks_it_cannot_run() {
+            let (mut sim, pallet) = setup();
+            let sender = act_as(&accounts().eve);
+            let refusals = [
+                (hook_memo(&accounts().django), "the receiver must be the hook contract"),
+                (r#"{"ink":5}"#.to_string(), "malformed ink memo"),
+            ];
+            for (memo, reason) in refusals {
+                let mut msg = transfer_msg("channel-0", &accounts().frank);
+                msg.memo = Some(memo);
+                let amount = Amount::native(100, "uatom");
+                sim.a
+                    .call(|app| app.execute_transfer(msg, amount, sender.clone()))
+                    .unwrap();
+                let outcomes = relay(&mut sim, &pallet);
+                let err = Error::InvalidHook {
+                    reason: reason.into(),
+                };
+                assert_eq!(acknowledgement(&outcomes[0]), ack_fail(&err));
+            }
+            // every send was refunded
+            assert_eq!(pallet.borrow().unescrowed.len(), 2);
+        }
+
+        #[ink::test]
+        fn only_the_contract_runs_hooks() {
+            let (mut sim, _pallet) = setup();
+            let frank = account_addr(&accounts().frank).into_string();
+            let amount = Amount::native(100, "uatom");
+            // the simulator's default caller is the contract account itself
+            ink::env::test::set_caller::<DefaultEnvironment>(accounts().eve);
+            let selector = [0x12, 0x34, 0x56, 0x78];
+            let channel = Some("channel-0".into());
+            let res = sim.a.call(|app| app.run_hook(frank, selector, vec![], amount, channel));
+            assert_eq!(res, Err(Error::Unauthorized));
+        }
+
+        #[ink::test]
+        fn mints_vouchers_for_hooks_before_calling_them() {
+            let (mut sim, pallet) = setup();
+            send_uatom(&mut sim, &pallet);
+            pallet.borrow_mut().failures.insert(0x20009, 14);
+            let voucher = DenomTrace::parse("transfer/channel-1/uatom").ibc_denom();
+            let frank = account_addr(&accounts().frank).into_string();
+            let amount = Amount::native(40, &voucher);
+            // b runs its own hooks, a failing mint never reaches the hook contract
+            act_as(&sim.b.account);
+            let res = sim
+                .b
+                .call(|app| app.run_hook(frank, [0x12, 0x34, 0x56, 0x78], vec![], amount, None));
+            assert_eq!(res, Err(Error::ChainExtension(IBCICS20Error::InvalidAddress)));
+        }
+
+        /// A `forward` memo sending the funds on to `receiver` over `channel`.
+        fn forward_memo(receiver: &AccountId, port: &str, channel: &str) -> String {
+            let receiver = account_addr(receiver).into_string();
+            format!(
+                r#"{{"forward":{{"receiver":"{}","port":"{}","channel":"{}"}}}}"#,
+                receiver, port, channel
+            )
+        }
+
+        /// Sends 40 of frank's vouchers from `b` back to django on `a`, with `memo`.
+        fn send_vouchers_home(sim: &mut Sim, memo: String) {
+            let voucher = DenomTrace::parse("transfer/channel-1/uatom").ibc_denom();
+            let mut msg = transfer_msg("channel-1", &accounts().django);
+            msg.memo = Some(memo);
+            let sender = act_as(&accounts().frank);
+            sim.b
+                .call(|app| app.execute_transfer(msg, Amount::native(40, &voucher), sender))
+                .unwrap();
+        }
+
+        #[ink::test]
+        fn forwards_and_holds_the_ack_until_the_next_hop_settles() {
+            let (mut sim, pallet) = setup();
+            send_uatom(&mut sim, &pallet);
+            // home to a and straight on to django on b again
+            let memo = forward_memo(&accounts().django, "transfer", "channel-0");
+            send_vouchers_home(&mut sim, memo);
+            let outcomes = relay(&mut sim, &pallet);
+
+            // the funds pass through the contract account
+            assert_eq!(pallet.borrow().unescrowed[0].receiver, sim.a.account);
+            assert!(matches!(outcomes[0], PacketOutcome::Received { .. }));
+            let forwarded = match &outcomes[1] {
+                PacketOutcome::Acknowledged { packet, .. } => {
+                    Ics20Packet::from_json(&packet.data).unwrap()
+                }
+                outcome => panic!("unexpected {:?}", outcome),
+            };
+            assert_eq!((forwarded.denom.as_str(), forwarded.amount), ("uatom", 40));
+            assert_eq!(forwarded.sender, account_addr(&sim.a.account).into_string());
+            // b gets the held ack once the forwarded packet is acknowledged
+            assert_eq!(acknowledgement(&outcomes[2]), ack_success());
+            assert_eq!(balances(&mut sim.a, "channel-0"), vec![("uatom".into(), 100)]);
+        }
+
+        #[ink::test]
+        fn refuses_forwards_it_cannot_make() {
+            let (mut sim, pallet) = setup();
+            send_uatom(&mut sim, &pallet);
+            let django = accounts().django;
+            let forward = forward_memo(&django, "transfer", "channel-0");
+            let with_hook = format!(
+                r#"{{"ink":{{"contract":"{}","selector":"0x12345678"}},{}"#,
+                account_addr(&django).into_string(),
+                &forward[1..]
+            );
+            let invalid = |reason: &str| Error::InvalidForward {
+                reason: reason.into(),
+            };
+            let refusals = [
+                (forward.replace("receiver", "to"), invalid("malformed forward memo")),
+                (
+                    forward_memo(&django, "nft", "channel-0"),
+                    invalid("can only forward over the transfer port"),
+                ),
+                (
+                    forward_memo(&django, "transfer", "channel-7"),
+                    Error::NoSuchChannel {
+                        id: "channel-7".into(),
+                    },
+                ),
+                (with_hook, invalid("cannot forward and call a hook")),
+            ];
+            for (memo, err) in refusals {
+                send_vouchers_home(&mut sim, memo);
+                let outcomes = relay(&mut sim, &pallet);
+                assert_eq!(acknowledgement(&outcomes[0]), ack_fail(&err));
+            }
+            assert!(pallet.borrow().unescrowed.is_empty());
+            assert_eq!(balances(&mut sim.a, "channel-0"), vec![("uatom".into(), 100)]);
+        }
+
+        #[ink::test]
+        fn unwinds_forwards_that_time_out() {
+            let (mut sim, pallet) = setup();
+            send_uatom(&mut sim, &pallet);
+            let memo = forward_memo(&accounts().django, "transfer", "channel-0");
+            send_vouchers_home(&mut sim, memo);
+            flush(&mut sim, &pallet);
+            let received = sim.relay().unwrap();
+            assert!(matches!(received[0], PacketOutcome::Received { .. }));
+            assert_eq!(balances(&mut sim.a, "channel-0"), vec![("uatom".into(), 100)]);
+
+            // the forwarded packet times out before it is relayed
+            sim.advance(1, 700);
+            let outcomes = relay(&mut sim, &pallet);
+            assert!(matches!(outcomes[0], PacketOutcome::TimedOut { .. }));
+            assert!(!acknowledgement(&outcomes[1]).is_success());
+            // the funds are back in escrow for the channel they came home over
+            assert_eq!(balances(&mut sim.a, "channel-0"), vec![("uatom".into(), 100)]);
+        }
+
+        #[ink::test]
+        fn returns_failed_forwards_to_the_inbound_escrow() {
+            let (mut sim, pallet) = setup();
+            let (channel_a, channel_b) = sim.open_channel(ICS20_ORDERING, ICS20_VERSION).unwrap();
+            assert_eq!((channel_a.as_str(), channel_b.as_str()), ("channel-2", "channel-3"));
+            send_uatom(&mut sim, &pallet);
+            let voucher = "transfer/channel-1/uatom";
+            let (contract, frank) = (sim.a.account, accounts().frank);
+            let held = |account: &AccountId, denom: &str| pallet.borrow().holding(account, denom);
+            // home to a over channel-0 and on to django on b over channel-2
+            let memo = forward_memo(&accounts().django, "transfer", "channel-2");
+
+            // the pallet refuses the forward, the funds go straight back into escrow
+            send_vouchers_home(&mut sim, memo.clone());
+            pallet.borrow_mut().failures.insert(0x20007, 15);
+            let outcomes = relay(&mut sim, &pallet);
+            pallet.borrow_mut().failures.clear();
+            assert!(!acknowledgement(&outcomes[0]).is_success());
+            assert_eq!(held(&escrow_account("channel-0"), "uatom"), 100);
+            assert_eq!(held(&contract, "uatom"), 0);
+            assert_eq!(held(&frank, voucher), 100);
+
+            // the forward goes out, then times out before it is relayed
+            send_vouchers_home(&mut sim, memo);
+            flush(&mut sim, &pallet);
+            let received = sim.relay().unwrap();
+            assert!(matches!(received[0], PacketOutcome::Received { .. }));
+            assert_eq!(held(&escrow_account("channel-0"), "uatom"), 60);
+            assert_eq!(held(&escrow_account("channel-2"), "uatom"), 40);
+            assert_eq!(held(&contract, "uatom"), 0);
+            sim.advance(1, 700);
+            let outcomes = relay(&mut sim, &pallet);
+            assert!(matches!(outcomes[0], PacketOutcome::TimedOut { .. }));
+            assert!(!acknowledgement(&outcomes[1]).is_success());
+
+            // the funds left channel-2 and are back in escrow for channel-0
+            assert_eq!(held(&escrow_account("channel-0"), "uatom"), 100);
+            assert_eq!(held(&escrow_account("channel-2"), "uatom"), 0);
+            assert_eq!(held(&contract, "uatom"), 0);
+            assert_eq!(held(&frank, voucher), 100);
+            assert_eq!(balances(&mut sim.a, "channel-0"), vec![("uatom".into(), 100)]);
+            assert_eq!(balances(&mut sim.a, "channel-2"), vec![("uatom".into(), 0)]);
+        }
+
+        fn quota(inflow: Option<QuotaLimit>, outflow: Option<QuotaLimit>) -> Quota {
+            Quota {
+                duration: 3600,
+                inflow,
+                outflow,
+            }
+        }
+
+        #[ink::test]
+        fn answers_receives_over_the_inflow_quota_with_an_error_ack() {
+            let (mut sim, pallet) = setup();
+            let voucher = DenomTrace::parse("transfer/channel-1/uatom").ibc_denom();
+            let limit = quota(Some(QuotaLimit::Absolute(150)), None);
+            as_gov(&mut sim.b, |app| app.set_quota("channel-1".into(), voucher.clone(), limit))
+                .unwrap();
+            assert_eq!(acknowledgement(&send_uatom(&mut sim, &pallet)[0]), ack_success());
+
+            let outcomes = send_uatom(&mut sim, &pallet);
+            let err = Error::RateLimitExceeded {
+                channel: "channel-1".into(),
+                denom: voucher.clone(),
+            };
+            assert_eq!(acknowledgement(&outcomes[0]), ack_fail(&err));
+            // the sender got the second send back
+            assert_eq!(pallet.borrow().unescrowed.len(), 1);
+            assert_eq!(balances(&mut sim.a, "channel-0"), vec![("uatom".into(), 100)]);
+            let limit = sim
+                .b
+                .call(|app| app.query_rate_limit("channel-1".into(), voucher.clone()))
+                .unwrap();
+            assert_eq!((limit.inflow, limit.inflow_left), (100, Some(50)));
+
+            // a new window lets it in again
+            sim.advance(1, 3600);
+            assert_eq!(acknowledgement(&send_uatom(&mut sim, &pallet)[0]), ack_success());
+        }
+
+        #[ink::test]
+        fn limits_the_outflow_until_reset() {
+            let (mut sim, pallet) = setup();
+            let limit = quota(None, Some(QuotaLimit::Absolute(150)));
+            as_gov(&mut sim.a, |app| app.set_quota("channel-0".into(), "uatom".into(), limit))
+                .unwrap();
+            send_uatom(&mut sim, &pallet);
+            let msg = transfer_msg("channel-0", &accounts().frank);
+            let sender = act_as(&accounts().eve);
+            let res = sim
+                .a
+                .call(|app| app.execute_transfer(msg, Amount::native(100, "uatom"), sender));
+            let err = Error::RateLimitExceeded {
+                channel: "channel-0".into(),
+                denom: "uatom".into(),
+            };
+            assert_eq!(res.err(), Some(err));
+
+            as_gov(&mut sim.a, |app| app.reset_quota("channel-0".into(), "uatom".into())).unwrap();
+            let limit = sim
+                .a
+                .call(|app| app.query_rate_limit("channel-0".into(), "uatom".into()))
+                .unwrap();
+            assert_eq!((limit.outflow, limit.channel_value), (0, 100));
+            send_uatom(&mut sim, &pallet);
+            as_gov(&mut sim.a, |app| app.remove_quota("channel-0".into(), "uatom".into())).unwrap();
+            let limit = sim.a.call(|app| app.query_rate_limit("channel-0".into(), "uatom".into()));
+            assert!(limit.is_none());
+            send_uatom(&mut sim, &pallet);
+            assert_eq!(balances(&mut sim.a, "channel-0"), vec![("uatom".into(), 300)]);
+        }
+
+        #[ink::test]
+        fn gov_sets_only_valid_quotas() {
+            let (mut sim, pallet) = setup();
+            let mut set = |channel: &str, quota: Quota| {
+                let channel = channel.to_string();
+                as_gov(&mut sim.a, |app| app.set_quota(channel, "uatom".into(), quota)).err()
+            };
+            let empty = Quota {
+                duration: 0,
+                inflow: None,
+                outflow: None,
+            };
+            let reason = "the window must not be empty".to_string();
+            assert_eq!(set("channel-0", empty), Some(Error::InvalidQuota { reason }));
+            let res = set("channel-0", quota(Some(QuotaLimit::Percent(101)), None));
+            let reason = "101% is more than the supply".to_string();
+            assert_eq!(res, Some(Error::InvalidQuota { reason }));
+            let res = set("channel-7", quota(None, None));
+            assert_eq!(res, Some(Error::NoSuchChannel { id: "channel-7".into() }));
+            // nothing is escrowed yet, so there is nothing to take a percentage of
+            let res = set("channel-0", quota(None, Some(QuotaLimit::Percent(50))));
+            let reason = "uatom has no value on channel-0 to take a percentage of".to_string();
+            assert_eq!(res, Some(Error::InvalidQuota { reason }));
+
+            let res = sim.a.call(|app| {
+                app.set_quota("channel-0".into(), "uatom".into(), quota(None, None))
+            });
+            assert_eq!(res.err(), Some(Error::Unauthorized));
+            let res = as_gov(&mut sim.a, |app| {
+                app.remove_quota("channel-0".into(), "uatom".into())
+            });
+            let reason = "no quota for uatom on channel-0".to_string();
+            assert_eq!(res.err(), Some(Error::InvalidQuota { reason }));
+
+            // once tokens are escrowed they can be limited by a percentage
+            send_uatom(&mut sim, &pallet);
+            let limit = quota(None, Some(QuotaLimit::Percent(50)));
+            as_gov(&mut sim.a, |app| app.set_quota("channel-0".into(), "uatom".into(), limit))
+                .unwrap();
+            let limit = sim
+                .a
+                .call(|app| app.query_rate_limit("channel-0".into(), "uatom".into()))
+                .unwrap();
+            assert_eq!((limit.channel_value, limit.outflow_left), (100, Some(50)));
+        }
+
+        #[ink::test]
+        fn tracks_channel_sequences() {
+            let (mut sim, pallet) = setup();
+            let sequences = |chain: &mut Chain<Contract>, channel: &str| {
+                chain.call(|app| app.query_sequences(channel.into())).unwrap()
+            };
+            assert_eq!(sequences(&mut sim.a, "channel-0"), ChannelSequences::new(ICS20_ORDERING));
+            assert_eq!(sim.a.call(|app| app.query_sequences("channel-7".into())), None);
+
+            send_uatom(&mut sim, &pallet);
+            let a = sequences(&mut sim.a, "channel-0");
+            assert_eq!((a.next_send, a.next_recv, a.next_ack), (2, 1, 2));
+            let b = sequences(&mut sim.b, "channel-1");
+            assert_eq!((b.next_send, b.next_recv, b.next_ack), (1, 2, 1));
+
+            // a timeout on an unordered channel leaves it open
+            let mut msg = transfer_msg("channel-0", &accounts().frank);
+            msg.timeout = Some(10);
+            let sender = act_as(&accounts().eve);
+            sim.a
+                .call(|app| app.execute_transfer(msg, Amount::native(100, "uatom"), sender))
+                .unwrap();
+            sim.advance(1, 20);
+            relay(&mut sim, &pallet);
+            let a = sequences(&mut sim.a, "channel-0");
+            assert_eq!((a.next_send, a.next_ack, a.closed), (3, 3, false));
+            assert_eq!(acknowledgement(&send_uatom(&mut sim, &pallet)[0]), ack_success());
+        }
+
+        type ContractEvent = <Contract as ink::reflect::ContractEventBase>::Type;
+
+        fn events() -> Vec<ContractEvent> {
+            ink::env::test::recorded_events()
+                .map(|event| ContractEvent::decode(&mut &event.data[..]).unwrap())
+                .collect()
+        }
+
+        fn event_name(event: &ContractEvent) -> &'static str {
+            match event {
+                ContractEvent::AdminUpdated(_) => "AdminUpdated",
+                ContractEvent::ConfigUpdated(_) => "ConfigUpdated",
+                ContractEvent::ChannelOpened(_) => "ChannelOpened",
+                ContractEvent::ChannelClosed(_) => "ChannelClosed",
+                ContractEvent::TransferSent(_) => "TransferSent",
+                ContractEvent::TransferReceived(_) => "TransferReceived",
+                ContractEvent::TransferAcknowledged(_) => "TransferAcknowledged",
+                ContractEvent::TransferTimedOut(_) => "TransferTimedOut",
+                ContractEvent::TransferRefunded(_) => "TransferRefunded",
+                ContractEvent::ContractAllowed(_) => "ContractAllowed",
+                ContractEvent::AssetRegistered(_) => "AssetRegistered",
+                ContractEvent::QuotaUpdated(_) => "QuotaUpdated",
+                ContractEvent::QuotaReset(_) => "QuotaReset",
+            }
+        }
+
+        /// The names of the events emitted after the first `skip`.
+        fn event_names(skip: usize) -> Vec<&'static str> {
+            events().iter().skip(skip).map(event_name).collect()
+        }
+
+        #[ink::test]
+        fn emits_the_lifecycle_of_transfers() {
+            let (mut sim, pallet) = setup();
+            assert_eq!(event_names(0), vec!["ChannelOpened", "ChannelOpened"]);
+
+            send_uatom(&mut sim, &pallet);
+            let names = ["TransferSent", "TransferReceived", "TransferAcknowledged"];
+            assert_eq!(event_names(2), names);
+            match &events()[2] {
+                ContractEvent::TransferSent(sent) => {
+                    assert_eq!((sent.channel.as_str(), sent.sequence), ("channel-0", 1));
+                    assert_eq!((sent.denom.as_str(), sent.amount), ("uatom", 100));
+                }
+                _ => panic!("expected TransferSent"),
+            }
+
+            let mut msg = transfer_msg("channel-0", &accounts().frank);
+            msg.memo = Some(r#"{"ink":5}"#.into());
+            let sender = act_as(&accounts().eve);
+            sim.a
+                .call(|app| app.execute_transfer(msg, Amount::native(100, "uatom"), sender))
+                .unwrap();
+            relay(&mut sim, &pallet);
+            let names = [
+                "TransferSent",
+                "TransferReceived",
+                "TransferAcknowledged",
+                "TransferRefunded",
+            ];
+            assert_eq!(event_names(5), names);
+            // b reports its error, a the error ack it got
+            let err = Error::InvalidHook {
+                reason: "malformed ink memo".into(),
+            };
+            match (&events()[6], &events()[7], ack_fail(&err)) {
+                (
+                    ContractEvent::TransferReceived(received),
+                    ContractEvent::TransferAcknowledged(acked),
+                    StdAck::Error(ack),
+                ) => {
+                    assert_eq!(received.error, Some(err.to_string()));
+                    assert_eq!(acked.error, Some(ack));
+                }
+                _ => panic!("expected the error ack"),
+            }
+
+            let mut msg = transfer_msg("channel-0", &accounts().frank);
+            msg.timeout = Some(10);
+            let sender = act_as(&accounts().eve);
+            sim.a
+                .call(|app| app.execute_transfer(msg, Amount::native(100, "uatom"), sender))
+                .unwrap();
+            sim.advance(1, 20);
+            relay(&mut sim, &pallet);
+            let names = ["TransferSent", "TransferTimedOut", "TransferRefunded"];
+            assert_eq!(event_names(9), names);
+            match &events()[11] {
+                ContractEvent::TransferRefunded(refunded) => {
+                    assert_eq!((refunded.sequence, refunded.amount), (3, 100));
+                    let eve = account_addr(&accounts().eve).into_string();
+                    assert_eq!(refunded.sender, eve);
+                }
+                _ => panic!("expected TransferRefunded"),
+            }
+        }
+
+        #[ink::test]
+        fn emits_admin_and_governance_changes() {
+            let (mut sim, _pallet) = setup();
+            let gov = account_addr(&accounts().charlie).into_string();
+            let token = account_addr(&AccountId::from([0xab; 32])).into_string();
+            sim.a.call(|app| app.update_config(30, None, gov)).unwrap();
+            let bob = account_addr(&accounts().bob).into_string();
+            sim.a.call(|app| app.update_admin(bob)).unwrap();
+            as_gov(&mut sim.a, |app| app.execute_allow(allow(&token, None))).unwrap();
+            as_gov(&mut sim.a, |app| app.register_asset(7, "dot".into())).unwrap();
+            as_gov(&mut sim.a, |app| {
+                app.set_quota("channel-0".into(), "uatom".into(), quota(None, None))
+            })
+            .unwrap();
+            as_gov(&mut sim.a, |app| app.reset_quota("channel-0".into(), "uatom".into())).unwrap();
+            as_gov(&mut sim.a, |app| app.remove_quota("channel-0".into(), "uatom".into())).unwrap();
+            // only the counterparty can close a channel
+            let channel = sim.b.channel("channel-1").unwrap().clone();
+            let closed = sim.close_channel("channel-0");
+            assert_eq!(closed, Err(trait_ibc::ibc::Error::CannotCloseChannel));
+            sim.b
+                .call(|app| app.ibc_channel_close(IbcChannelCloseMsg::CloseConfirm { channel }))
+                .unwrap();
+
+            let names = [
+                "ConfigUpdated",
+                "AdminUpdated",
+                "ContractAllowed",
+                "AssetRegistered",
+                "QuotaUpdated",
+                "QuotaReset",
+                "QuotaUpdated",
+                "ChannelClosed",
+            ];
+            assert_eq!(event_names(2), names);
+            let events = events();
+            match (&events[3], &events[6], &events[8]) {
+                (
+                    ContractEvent::AdminUpdated(admin),
+                    ContractEvent::QuotaUpdated(set),
+                    ContractEvent::QuotaUpdated(removed),
+                ) => {
+                    assert_eq!(admin.admin, account_addr(&accounts().bob));
+                    assert_eq!(set.quota, Some(quota(None, None)));
+                    assert_eq!(removed.quota, None);
+                }
+                _ => panic!("unexpected events"),
+            }
+        }
     }
 }
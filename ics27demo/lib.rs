@@ -7,6 +7,17 @@ mod ics27 {
     use ink::prelude::{string::String, string::ToString, vec::Vec};
     use scale::{Decode, Encode};
 
+    pub const ICS27_VERSION: &str = "ics27-1";
+    pub const ICS27_ORDERING: IbcOrder = IbcOrder::Ordered;
+
+    /// Interchain account channels are ordered and opened by a controller port
+    /// (`icacontroller-{owner}`).
+    fn handshake_policy() -> HandshakePolicy {
+        HandshakePolicy::new(ICS27_VERSION)
+            .with_order(ICS27_ORDERING)
+            .with_counterparty_port(PortRule::Prefix("icacontroller-".into()))
+    }
+
     #[derive(Decode, Encode)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub struct InstantiateMsg {
@@ -213,6 +224,8 @@ mod ics27 {
         prefix_accounts: Vec<u8>,
         ///result static string "result"
         result_prefix: Vec<u8>,
+        /// next send, receive and ack sequence of every channel
+        sequences: SequenceTracker,
     }
 
     // pub enum ReflectExecuteMsg {
@@ -268,6 +281,11 @@ mod ics27 {
         SubMsgResult {
             id: u64,
         },
+        /// The next send, receive and ack sequence of the channel
+        //#[returns(Option<ChannelSequences>)]
+        Sequences {
+            channel_id: String,
+        },
     }
 
     #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
@@ -285,6 +303,51 @@ mod ics27 {
         MessagesEmpty,
     }
 
+    /// A controller finished the channel handshake with us.
+    #[ink(event)]
+    pub struct ChannelOpened {
+        #[ink(topic)]
+        channel: String,
+        #[ink(topic)]
+        counterparty_port: String,
+        version: String,
+    }
+
+    /// A channel was closed.
+    #[ink(event)]
+    pub struct ChannelClosed {
+        #[ink(topic)]
+        channel: String,
+    }
+
+    /// A packet came in from the controller.
+    #[ink(event)]
+    pub struct PacketReceived {
+        #[ink(topic)]
+        channel: String,
+        #[ink(topic)]
+        sequence: u64,
+    }
+
+    /// A packet was acknowledged, `error` is set for an error ack.
+    #[ink(event)]
+    pub struct PacketAcknowledged {
+        #[ink(topic)]
+        channel: String,
+        #[ink(topic)]
+        sequence: u64,
+        error: Option<String>,
+    }
+
+    /// A packet timed out.
+    #[ink(event)]
+    pub struct PacketTimedOut {
+        #[ink(topic)]
+        channel: String,
+        #[ink(topic)]
+        sequence: u64,
+    }
+
     impl BaseIbc for Ics27demo {
         // ibc base function
         #[ink(message)]
@@ -309,20 +372,30 @@ mod ics27 {
 
         #[ink(message)]
         fn ibc_channel_open(&self, msg: IbcChannelOpenMsg) -> Result<IbcChannelOpenResponse, ibc::ibc::Error> {
-            Ok(())
+            handshake_policy().open_response(&msg)
         }
 
         #[ink(message)]
         fn ibc_channel_connect(&mut self, msg: IbcChannelConnectMsg) -> Result<IbcBasicResponse, ibc::ibc::Error> {
-            Ok(IbcBasicResponse {
-                messages: Vec::new(),
-                attributes: Vec::new(),
-                events: Vec::new(),
-            })
+            let version = handshake_policy().validate_connect(&msg)?;
+            self.sequences.open(&msg);
+            let channel = msg.channel();
+            self.env().emit_event(ChannelOpened {
+                channel: channel.endpoint.channel_id.clone(),
+                counterparty_port: channel.counterparty_endpoint.port_id.clone(),
+                version: version.clone(),
+            });
+            Ok(IbcBasicResponse::new()
+                .add_attribute("action", "ibc_connect")
+                .add_attribute("channel_id", msg.channel().endpoint.channel_id.clone())
+                .add_attribute("version", version))
         }
 
         #[ink(message)]
         fn ibc_channel_close(&self, msg: IbcChannelCloseMsg) -> Result<IbcBasicResponse, ibc::ibc::Error> {
+            self.env().emit_event(ChannelClosed {
+                channel: msg.channel().endpoint.channel_id.clone(),
+            });
             Ok(IbcBasicResponse {
                 messages: Vec::new(),
                 attributes: Vec::new(),
@@ -335,8 +408,15 @@ mod ics27 {
             &mut self,
             msg: IbcPacketReceiveMsg,
         ) -> Result<IbcReceiveResponse, ibc::ibc::Error> {
+            // ICA channels are ordered, a packet delivered out of turn must not run
+            self.sequences.on_receive(&msg.packet)?;
+            self.env().emit_event(PacketReceived {
+                channel: msg.packet.dest.channel_id.clone(),
+                sequence: msg.packet.sequence,
+            });
+            // the demo host runs no messages, an empty result acknowledges the packet at once
             Ok(IbcReceiveResponse {
-                acknowledgement: Vec::new(),
+                acknowledgement: StdAck::success(Binary::default()).into(),
                 messages: Vec::new(),
                 attributes: Vec::new(),
                 events: Vec::new(),
@@ -346,20 +426,39 @@ mod ics27 {
         #[ink(message)]
         fn ibc_packet_ack(
             &mut self,
-            _msg: IbcPacketAckMsg,
+            msg: IbcPacketAckMsg,
         ) -> Result<IbcBasicResponse, ibc::ibc::Error> {
-            Ok(IbcBasicResponse {
-                messages: Vec::new(),
-                attributes: Vec::new(),
-                events: Vec::new(),
-            })
+            self.sequences.on_ack(&msg.original_packet)?;
+            let res = IbcBasicResponse::new()
+                .add_attribute("action", "acknowledge")
+                .add_attribute("sequence", msg.original_packet.sequence.to_string());
+            let ack = msg.acknowledgement.std_ack()?;
+            self.env().emit_event(PacketAcknowledged {
+                channel: msg.original_packet.src.channel_id.clone(),
+                sequence: msg.original_packet.sequence,
+                error: match &ack {
+                    StdAck::Result(_) => None,
+                    StdAck::Error(err) => Some(err.clone()),
+                },
+            });
+            match ack {
+                StdAck::Result(_) => Ok(res.add_attribute("success", "true")),
+                StdAck::Error(err) => Ok(res
+                    .add_attribute("success", "false")
+                    .add_attribute("error", err)),
+            }
         }
 
         #[ink(message)]
         fn ibc_packet_timeout(
             &mut self,
-            _msg: IbcPacketTimeoutMsg,
+            msg: IbcPacketTimeoutMsg,
         ) -> Result<IbcBasicResponse, ibc::ibc::Error> {
+            self.sequences.on_timeout(&msg.packet)?;
+            self.env().emit_event(PacketTimedOut {
+                channel: msg.packet.src.channel_id.clone(),
+                sequence: msg.packet.sequence,
+            });
             Ok(IbcBasicResponse {
                 messages: Vec::new(),
                 attributes: Vec::new(),
@@ -377,6 +476,7 @@ mod ics27 {
                 key_pending_channel: Default::default(),
                 prefix_accounts: Default::default(),
                 result_prefix: Default::default(),
+                sequences: Default::default(),
             }
         }
 
@@ -510,6 +610,12 @@ mod ics27 {
                 data: Vec::<u8>::new(),
             }
         }
+
+        /// The next send, receive and ack sequence of the channel, for finding stuck packets
+        #[ink(message)]
+        pub fn query_sequences(&self, channel_id: String) -> Option<ChannelSequences> {
+            self.sequences.get(&channel_id)
+        }
     }
 
     /// Unit tests in Rust are normally defined within such a `#[cfg(test)]`
@@ -519,5 +625,87 @@ mod ics27 {
     mod tests {
         /// Imports all the definitions from the outer scope so we can use them here.
         use super::*;
+        use ibc::testing::{Chain, IbcSimulator, PacketOutcome};
+
+        type ContractEvent = <Ics27demo as ink::reflect::ContractEventBase>::Type;
+
+        fn simulator(port_a: &str, port_b: &str) -> IbcSimulator<Ics27demo, Ics27demo> {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let a = Chain::instantiate(accounts.alice, port_a, Ics27demo::default);
+            let b = Chain::instantiate(accounts.bob, port_b, Ics27demo::default);
+            IbcSimulator::new(a, b)
+        }
+
+        #[ink::test]
+        fn opens_ordered_channels_with_controllers() {
+            let mut sim = simulator("icacontroller-alice", "icacontroller-bob");
+            let (channel_a, channel_b) = sim.open_channel(ICS27_ORDERING, ICS27_VERSION).unwrap();
+            assert_eq!(sim.b.channel(&channel_b).unwrap().version, ICS27_VERSION);
+            assert_eq!(
+                sim.a.channel(&channel_a).unwrap().counterparty_endpoint.port_id,
+                "icacontroller-bob"
+            );
+
+            assert_eq!(
+                sim.open_channel(IbcOrder::Unordered, ICS27_VERSION),
+                Err(ibc::ibc::Error::OnlyOrderedChannel)
+            );
+        }
+
+        #[ink::test]
+        fn receives_packets_in_sequence() {
+            let mut sim = simulator("icacontroller-alice", "icacontroller-bob");
+            let (channel_a, channel_b) = sim.open_channel(ICS27_ORDERING, ICS27_VERSION).unwrap();
+            let timeout = IbcTimeout::with_timestamp(sim.b.time.plus_seconds(3600).unwrap());
+            sim.a.send_packet(&channel_a, b"{}".to_vec(), timeout.clone());
+            sim.a.send_packet(&channel_a, b"{}".to_vec(), timeout.clone());
+            let outcomes = sim.relay().unwrap();
+            assert_eq!(outcomes.len(), 2);
+            let (packet, ack) = match &outcomes[0] {
+                PacketOutcome::Acknowledged {
+                    packet,
+                    acknowledgement,
+                } => (packet.clone(), acknowledgement.clone()),
+                outcome => panic!("not acknowledged: {:?}", outcome),
+            };
+            assert_eq!(ack, Binary::from(StdAck::success(Binary::default())));
+            let sequences = sim.b.call(|app| app.query_sequences(channel_b.clone())).unwrap();
+            assert_eq!(sequences.order, IbcOrder::Ordered);
+            assert_eq!(sequences.next_recv, 3);
+            let received: Vec<u64> = ink::env::test::recorded_events()
+                .filter_map(|event| match ContractEvent::decode(&mut &event.data[..]).unwrap() {
+                    ContractEvent::PacketReceived(received) if received.channel == channel_b => {
+                        Some(received.sequence)
+                    }
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(received, vec![1, 2]);
+
+            // a replayed packet is refused
+            #[cfg(feature = "ibc3")]
+            let msg = IbcPacketReceiveMsg::new(packet, Addr::unchecked("relayer"));
+            #[cfg(not(feature = "ibc3"))]
+            let msg = IbcPacketReceiveMsg::new(packet);
+            assert_eq!(
+                sim.b.call(|app| app.ibc_packet_receive(msg)).err(),
+                Some(ibc::ibc::Error::PacketOutOfOrder {
+                    channel: channel_b,
+                    expected: 3,
+                    actual: 1,
+                })
+            );
+        }
+
+        #[ink::test]
+        fn rejects_counterparties_that_are_not_controllers() {
+            let mut sim = simulator("icacontroller-alice", "transfer");
+            assert_eq!(
+                sim.open_channel(ICS27_ORDERING, ICS27_VERSION),
+                Err(ibc::ibc::Error::InvalidCounterpartyPort {
+                    port: "transfer".into()
+                })
+            );
+        }
     }
 }
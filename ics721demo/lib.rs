@@ -11,6 +11,16 @@ mod ics721demo {
     use ink::storage::Mapping;
     use scale::{Decode, Encode};
 
+    pub const ICS721_VERSION: &str = "ics721-1";
+    pub const ICS721_ORDERING: IbcOrder = IbcOrder::Unordered;
+
+    fn handshake_policy() -> HandshakePolicy {
+        HandshakePolicy::new(ICS721_VERSION).with_order(ICS721_ORDERING)
+    }
+
+    #[cfg(feature = "std")]
+    use ink::storage::traits::StorageLayout;
+
     #[derive(Decode, Encode)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub struct TokenId(String);
@@ -31,11 +41,11 @@ mod ics721demo {
     /// used here to provide some distinction between token and class IDs
     /// in the type system.
     #[derive(Decode, Encode)]
-    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    #[cfg_attr(feature = "std", derive(StorageLayout, scale_info::TypeInfo))]
     pub struct ClassId(String);
 
     #[derive(Decode, Encode)]
-    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    #[cfg_attr(feature = "std", derive(StorageLayout, scale_info::TypeInfo))]
     pub struct Class {
         /// A unique (from the source chain's perspective) identifier for
         /// the class.
@@ -343,8 +353,8 @@ mod ics721demo {
         Proxy {},
     }
 
-    #[derive(Decode, Encode, Default)]
-    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    #[derive(Decode, Encode, Default, Debug)]
+    #[cfg_attr(feature = "std", derive(StorageLayout, scale_info::TypeInfo))]
     pub struct PauseOrchestrator {
         pauser: Option<Addr>,
         paused: bool,
@@ -438,6 +448,51 @@ mod ics721demo {
         token_metadata: Mapping<(ClassId, TokenId), Option<Vec<u8>>>,
     }
 
+    /// A channel finished its handshake with us.
+    #[ink(event)]
+    pub struct ChannelOpened {
+        #[ink(topic)]
+        channel: String,
+        #[ink(topic)]
+        counterparty_port: String,
+        version: String,
+    }
+
+    /// A channel was closed.
+    #[ink(event)]
+    pub struct ChannelClosed {
+        #[ink(topic)]
+        channel: String,
+    }
+
+    /// An NFT packet came in.
+    #[ink(event)]
+    pub struct PacketReceived {
+        #[ink(topic)]
+        channel: String,
+        #[ink(topic)]
+        sequence: u64,
+    }
+
+    /// An NFT packet was acknowledged, `error` is set for an error ack.
+    #[ink(event)]
+    pub struct PacketAcknowledged {
+        #[ink(topic)]
+        channel: String,
+        #[ink(topic)]
+        sequence: u64,
+        error: Option<String>,
+    }
+
+    /// An NFT packet timed out.
+    #[ink(event)]
+    pub struct PacketTimedOut {
+        #[ink(topic)]
+        channel: String,
+        #[ink(topic)]
+        sequence: u64,
+    }
+
     impl BaseIbc for Ics721demo {
         // ibc base function
         #[ink(message)]
@@ -465,7 +520,7 @@ mod ics721demo {
             &self,
             msg: IbcChannelOpenMsg,
         ) -> Result<IbcChannelOpenResponse, ibc::ibc::Error> {
-            Ok(())
+            handshake_policy().open_response(&msg)
         }
 
         #[ink(message)]
@@ -473,11 +528,17 @@ mod ics721demo {
             &mut self,
             msg: IbcChannelConnectMsg,
         ) -> Result<IbcBasicResponse, ibc::ibc::Error> {
-            Ok(IbcBasicResponse {
-                messages: Vec::new(),
-                attributes: Vec::new(),
-                events: Vec::new(),
-            })
+            let version = handshake_policy().validate_connect(&msg)?;
+            let channel = msg.channel();
+            self.env().emit_event(ChannelOpened {
+                channel: channel.endpoint.channel_id.clone(),
+                counterparty_port: channel.counterparty_endpoint.port_id.clone(),
+                version: version.clone(),
+            });
+            Ok(IbcBasicResponse::new()
+                .add_attribute("action", "ibc_connect")
+                .add_attribute("channel_id", msg.channel().endpoint.channel_id.clone())
+                .add_attribute("version", version))
         }
 
         #[ink(message)]
@@ -485,6 +546,9 @@ mod ics721demo {
             &self,
             msg: IbcChannelCloseMsg,
         ) -> Result<IbcBasicResponse, ibc::ibc::Error> {
+            self.env().emit_event(ChannelClosed {
+                channel: msg.channel().endpoint.channel_id.clone(),
+            });
             Ok(IbcBasicResponse {
                 messages: Vec::new(),
                 attributes: Vec::new(),
@@ -497,8 +561,13 @@ mod ics721demo {
             &mut self,
             msg: IbcPacketReceiveMsg,
         ) -> Result<IbcReceiveResponse, ibc::ibc::Error> {
+            self.env().emit_event(PacketReceived {
+                channel: msg.packet.dest.channel_id.clone(),
+                sequence: msg.packet.sequence,
+            });
+            // the ICS-721 success ack, the demo mints nothing on receive
             Ok(IbcReceiveResponse {
-                acknowledgement: Vec::new(),
+                acknowledgement: StdAck::success([1u8]).into(),
                 messages: Vec::new(),
                 attributes: Vec::new(),
                 events: Vec::new(),
@@ -508,20 +577,37 @@ mod ics721demo {
         #[ink(message)]
         fn ibc_packet_ack(
             &mut self,
-            _msg: IbcPacketAckMsg,
+            msg: IbcPacketAckMsg,
         ) -> Result<IbcBasicResponse, ibc::ibc::Error> {
-            Ok(IbcBasicResponse {
-                messages: Vec::new(),
-                attributes: Vec::new(),
-                events: Vec::new(),
-            })
+            let res = IbcBasicResponse::new()
+                .add_attribute("action", "acknowledge")
+                .add_attribute("sequence", msg.original_packet.sequence.to_string());
+            let ack = msg.acknowledgement.std_ack()?;
+            self.env().emit_event(PacketAcknowledged {
+                channel: msg.original_packet.src.channel_id.clone(),
+                sequence: msg.original_packet.sequence,
+                error: match &ack {
+                    StdAck::Result(_) => None,
+                    StdAck::Error(err) => Some(err.clone()),
+                },
+            });
+            match ack {
+                StdAck::Result(_) => Ok(res.add_attribute("success", "true")),
+                StdAck::Error(err) => Ok(res
+                    .add_attribute("success", "false")
+                    .add_attribute("error", err)),
+            }
         }
 
         #[ink(message)]
         fn ibc_packet_timeout(
             &mut self,
-            _msg: IbcPacketTimeoutMsg,
+            msg: IbcPacketTimeoutMsg,
         ) -> Result<IbcBasicResponse, ibc::ibc::Error> {
+            self.env().emit_event(PacketTimedOut {
+                channel: msg.packet.src.channel_id.clone(),
+                sequence: msg.packet.sequence,
+            });
             Ok(IbcBasicResponse {
                 messages: Vec::new(),
                 attributes: Vec::new(),
@@ -653,7 +739,7 @@ mod ics721demo {
         /// query contract by class id
         #[ink(message)]
         pub fn query_nft_contract_for_class_id(&self, class_id: String) -> Option<Addr> {
-            Some(Addr("".to_string()))
+            Some(Addr::unchecked(""))
         }
 
         /// query class metadata
@@ -677,7 +763,7 @@ mod ics721demo {
         /// query pauser admin
         #[ink(message)]
         pub fn query_pauser(&self) -> Option<Addr> {
-            Some(Addr("".to_string()))
+            Some(Addr::unchecked(""))
         }
 
         /// query if contract is paused
@@ -690,7 +776,7 @@ mod ics721demo {
         /// The proxy that this contract is receiving NFTs from, if any.
         #[ink(message)]
         pub fn query_proxy(&self) -> Option<Addr> {
-            Some(Addr("".to_string()))
+            Some(Addr::unchecked(""))
         }
     }
 
@@ -701,5 +787,68 @@ mod ics721demo {
     mod tests {
         /// Imports all the definitions from the outer scope so we can use them here.
         use super::*;
+        use ibc::testing::{Chain, IbcSimulator, PacketOutcome};
+
+        type ContractEvent = <Ics721demo as ink::reflect::ContractEventBase>::Type;
+
+        fn simulator() -> IbcSimulator<Ics721demo, Ics721demo> {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let a = Chain::instantiate(accounts.alice, "ics721-a", Ics721demo::default);
+            let b = Chain::instantiate(accounts.bob, "ics721-b", Ics721demo::default);
+            IbcSimulator::new(a, b)
+        }
+
+        #[ink::test]
+        fn opens_unordered_ics721_channels_only() {
+            let mut sim = simulator();
+            let (channel_a, _) = sim.open_channel(ICS721_ORDERING, ICS721_VERSION).unwrap();
+            assert_eq!(sim.a.channel(&channel_a).unwrap().version, ICS721_VERSION);
+
+            assert_eq!(
+                sim.open_channel(IbcOrder::Ordered, ICS721_VERSION),
+                Err(ibc::ibc::Error::OrderedChannel)
+            );
+            assert!(sim.open_channel(ICS721_ORDERING, "ics20-1").is_err());
+        }
+
+        #[ink::test]
+        fn acknowledges_received_packets() {
+            let mut sim = simulator();
+            let (channel_a, _) = sim.open_channel(ICS721_ORDERING, ICS721_VERSION).unwrap();
+            let timeout = IbcTimeout::with_timestamp(sim.a.time.plus_seconds(5).unwrap());
+            sim.a.send_packet(&channel_a, b"{}".to_vec(), timeout);
+
+            let outcomes = sim.relay().unwrap();
+            assert!(matches!(
+                &outcomes[..],
+                [PacketOutcome::Acknowledged { acknowledgement, .. }]
+                    if *acknowledgement == Binary::from(StdAck::success([1u8]))
+            ));
+        }
+
+        #[ink::test]
+        fn expired_packets_time_out() {
+            let mut sim = simulator();
+            let (channel_a, _) = sim.open_channel(ICS721_ORDERING, ICS721_VERSION).unwrap();
+            let timeout = IbcTimeout::with_timestamp(sim.a.time.plus_seconds(5).unwrap());
+            sim.a.send_packet(&channel_a, b"{}".to_vec(), timeout);
+
+            sim.advance(1, 5);
+            let outcomes = sim.relay().unwrap();
+            assert!(matches!(outcomes[..], [PacketOutcome::TimedOut { .. }]));
+
+            let events: Vec<ContractEvent> = ink::env::test::recorded_events()
+                .map(|event| ContractEvent::decode(&mut &event.data[..]).unwrap())
+                .collect();
+            assert!(matches!(
+                &events[..],
+                [
+                    ContractEvent::ChannelOpened(_),
+                    ContractEvent::ChannelOpened(_),
+                    ContractEvent::PacketTimedOut(timed_out),
+                ]
+                    if timed_out.channel == channel_a && timed_out.sequence == 1
+            ));
+        }
     }
 }
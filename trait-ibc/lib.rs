@@ -1,16 +1,24 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 #![feature(default_alloc_error_handler)]
 
+#[cfg(feature = "test-utils")]
+pub mod testing;
+
 #[ink::contract]
 pub mod ibc {
-    use ink::prelude::{string::String, vec, vec::Vec};
+    use ink::prelude::{
+        string::{String, ToString},
+        vec,
+        vec::Vec,
+    };
     use scale::{Decode, Encode};
     use serde::{Deserialize, Serialize};
 
     #[cfg(feature = "std")]
     use ink::storage::traits::StorageLayout;
 
-    #[derive(Decode, Encode, Default, Clone)]
+    #[derive(Decode, Encode, Serialize, Deserialize, Default, Clone, Debug)]
+    #[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
     #[cfg_attr(feature = "std", derive(StorageLayout, scale_info::TypeInfo))]
     pub struct Addr(String);
 
@@ -52,27 +60,131 @@ pub mod ibc {
         pub fn into_string(self) -> String {
             self.0
         }
+
+        /// Parses an address rendered by `From<AccountId>` back into the account.
+        pub fn to_account_id(&self) -> Option<AccountId> {
+            let hex = self.0.strip_prefix("0x")?.as_bytes();
+            if hex.len() != 64 {
+                return None;
+            }
+            let mut bytes = [0u8; 32];
+            for (byte, pair) in bytes.iter_mut().zip(hex.chunks(2)) {
+                let high = (pair[0] as char).to_digit(16)?;
+                let low = (pair[1] as char).to_digit(16)?;
+                *byte = (high << 4 | low) as u8;
+            }
+            Some(AccountId::from(bytes))
+        }
+    }
+
+    /// Contract accounts are known by `0x` followed by the lowercase hex encoded `AccountId`.
+    impl From<ink::primitives::AccountId> for Addr {
+        fn from(account: ink::primitives::AccountId) -> Self {
+            const HEX: &[u8; 16] = b"0123456789abcdef";
+            let bytes: &[u8] = account.as_ref();
+            let mut addr = String::with_capacity(2 + bytes.len() * 2);
+            addr.push_str("0x");
+            for b in bytes {
+                addr.push(HEX[(b >> 4) as usize] as char);
+                addr.push(HEX[(b & 0xf) as usize] as char);
+            }
+            Addr(addr)
+        }
+    }
+
+    /// Binary is a wrapper around `Vec<u8>` to add base64 de/serialization with serde,
+    /// the way CosmWasm and the Cosmos SDK render `[]byte` in JSON.
+    /// SCALE encodes it exactly like the underlying `Vec<u8>`.
+    #[derive(Decode, Encode, Default, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    #[cfg_attr(feature = "std", derive(StorageLayout, scale_info::TypeInfo))]
+    pub struct Binary(pub Vec<u8>);
+
+    impl Binary {
+        /// Standard, padded base64 as produced by Go's `encoding/json`.
+        pub fn to_base64(&self) -> String {
+            base64_bytes::encode(&self.0)
+        }
+
+        pub fn from_base64(encoded: &str) -> Result<Self, Error> {
+            base64_bytes::decode(encoded)
+                .map(Binary)
+                .map_err(|_| Error::ParseError)
+        }
+
+        pub fn as_slice(&self) -> &[u8] {
+            self.0.as_slice()
+        }
+
+        pub fn to_vec(&self) -> Vec<u8> {
+            self.0.clone()
+        }
+    }
+
+    impl core::ops::Deref for Binary {
+        type Target = [u8];
+
+        fn deref(&self) -> &Self::Target {
+            self.as_slice()
+        }
+    }
+
+    impl From<Vec<u8>> for Binary {
+        fn from(data: Vec<u8>) -> Self {
+            Binary(data)
+        }
+    }
+
+    impl From<&[u8]> for Binary {
+        fn from(data: &[u8]) -> Self {
+            Binary(data.to_vec())
+        }
+    }
+
+    impl<const LENGTH: usize> From<[u8; LENGTH]> for Binary {
+        fn from(data: [u8; LENGTH]) -> Self {
+            Binary(data.to_vec())
+        }
+    }
+
+    impl From<Binary> for Vec<u8> {
+        fn from(data: Binary) -> Self {
+            data.0
+        }
+    }
+
+    impl Serialize for Binary {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            base64_bytes::serialize(&self.0, serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Binary {
+        fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            base64_bytes::deserialize(deserializer).map(Binary)
+        }
     }
 
-    #[derive(Decode, Encode, Clone)]
+    #[derive(Decode, Encode, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
     #[cfg_attr(feature = "std", derive(StorageLayout, scale_info::TypeInfo))]
     pub struct IbcEndpoint {
         pub port_id: String,
         pub channel_id: String,
     }
 
-    #[derive(Decode, Encode, Clone, PartialEq, Eq)]
-    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    #[derive(Decode, Encode, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(StorageLayout, scale_info::TypeInfo))]
     pub enum IbcOrder {
+        #[serde(rename = "ORDER_UNORDERED")]
         Unordered,
+        #[serde(rename = "ORDER_ORDERED")]
         Ordered,
     }
 
-    #[derive(Decode, Encode)]
+    #[derive(Decode, Encode, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub struct IbcPacket {
         /// The raw data sent from the other side in the packet
-        pub data: Vec<u8>,
+        pub data: Binary,
         /// identifies the channel and port on the sending chain.
         pub src: IbcEndpoint,
         /// identifies the channel and port on the receiving chain.
@@ -82,14 +194,166 @@ pub mod ibc {
         pub timeout: IbcTimeout,
     }
 
-    #[derive(Decode, Encode, Serialize, Deserialize)]
+    /// In IBC each packet must set at least one type of timeout:
+    /// the timestamp or the block height. The constructors below always set at least one.
+    #[derive(Decode, Encode, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub struct IbcTimeout {
         block: Option<IbcTimeoutBlock>,
-        timestamp: Option<Timestamp>,
+        timestamp: Option<IbcTimestamp>,
     }
 
-    #[derive(Decode, Encode, Serialize, Deserialize)]
+    impl IbcTimeout {
+        /// Times out once the counterparty reaches the given block.
+        pub fn with_block(block: IbcTimeoutBlock) -> Self {
+            IbcTimeout {
+                block: Some(block),
+                timestamp: None,
+            }
+        }
+
+        /// Times out once the counterparty's block time reaches `timestamp`.
+        pub fn with_timestamp(timestamp: IbcTimestamp) -> Self {
+            IbcTimeout {
+                block: None,
+                timestamp: Some(timestamp),
+            }
+        }
+
+        /// Times out at whichever of the two is reached first.
+        pub fn with_both(block: IbcTimeoutBlock, timestamp: IbcTimestamp) -> Self {
+            IbcTimeout {
+                block: Some(block),
+                timestamp: Some(timestamp),
+            }
+        }
+
+        pub fn block(&self) -> Option<IbcTimeoutBlock> {
+            self.block
+        }
+
+        pub fn timestamp(&self) -> Option<IbcTimestamp> {
+            self.timestamp
+        }
+
+        /// Applies the ICS-04 receive rules against the counterparty's current height and time.
+        ///
+        /// A packet is expired once the height reaches the timeout height or the time
+        /// reaches the timeout timestamp. A zero height or timestamp disables that check,
+        /// like an unset one does.
+        pub fn is_expired(&self, current_height: &IbcTimeoutBlock, current_time: IbcTimestamp) -> bool {
+            let height_expired = match self.block {
+                Some(block) => !block.is_zero() && *current_height >= block,
+                None => false,
+            };
+            let time_expired = match self.timestamp {
+                Some(timestamp) => !timestamp.is_zero() && current_time >= timestamp,
+                None => false,
+            };
+            height_expired || time_expired
+        }
+    }
+
+    impl From<IbcTimeoutBlock> for IbcTimeout {
+        fn from(block: IbcTimeoutBlock) -> IbcTimeout {
+            IbcTimeout::with_block(block)
+        }
+    }
+
+    impl From<IbcTimestamp> for IbcTimeout {
+        fn from(timestamp: IbcTimestamp) -> IbcTimeout {
+            IbcTimeout::with_timestamp(timestamp)
+        }
+    }
+
+    /// A point in time in nanoseconds since the UNIX epoch, the unit ICS-04 timeouts use.
+    ///
+    /// ink! block time (`Self::env().block_timestamp()`) is in milliseconds, so always go
+    /// through `from_millis` when turning it into an IBC timestamp.
+    /// Serializes as a decimal string, like CosmWasm's `Timestamp`.
+    #[derive(Decode, Encode, Default, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+    #[cfg_attr(feature = "std", derive(StorageLayout, scale_info::TypeInfo))]
+    pub struct IbcTimestamp(u64);
+
+    impl IbcTimestamp {
+        pub const fn from_nanos(nanos: u64) -> Self {
+            IbcTimestamp(nanos)
+        }
+
+        /// Returns `None` if the result does not fit into `u64` nanoseconds.
+        pub fn from_millis(millis: u64) -> Option<Self> {
+            millis.checked_mul(1_000_000).map(IbcTimestamp)
+        }
+
+        /// Returns `None` if the result does not fit into `u64` nanoseconds.
+        pub fn from_seconds(seconds: u64) -> Option<Self> {
+            seconds.checked_mul(1_000_000_000).map(IbcTimestamp)
+        }
+
+        pub const fn nanos(&self) -> u64 {
+            self.0
+        }
+
+        /// Whole milliseconds, rounded down.
+        pub const fn millis(&self) -> u64 {
+            self.0 / 1_000_000
+        }
+
+        /// Whole seconds, rounded down.
+        pub const fn seconds(&self) -> u64 {
+            self.0 / 1_000_000_000
+        }
+
+        /// A zero timestamp means "no timestamp timeout" in ICS-04.
+        pub const fn is_zero(&self) -> bool {
+            self.0 == 0
+        }
+
+        pub fn plus_nanos(&self, nanos: u64) -> Option<Self> {
+            self.0.checked_add(nanos).map(IbcTimestamp)
+        }
+
+        pub fn plus_millis(&self, millis: u64) -> Option<Self> {
+            self.plus_nanos(millis.checked_mul(1_000_000)?)
+        }
+
+        pub fn plus_seconds(&self, seconds: u64) -> Option<Self> {
+            self.plus_nanos(seconds.checked_mul(1_000_000_000)?)
+        }
+
+        pub fn minus_nanos(&self, nanos: u64) -> Option<Self> {
+            self.0.checked_sub(nanos).map(IbcTimestamp)
+        }
+
+        pub fn minus_millis(&self, millis: u64) -> Option<Self> {
+            self.minus_nanos(millis.checked_mul(1_000_000)?)
+        }
+
+        pub fn minus_seconds(&self, seconds: u64) -> Option<Self> {
+            self.minus_nanos(seconds.checked_mul(1_000_000_000)?)
+        }
+    }
+
+    impl Serialize for IbcTimestamp {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_str(&self.0.to_string())
+        }
+    }
+
+    impl<'de> Deserialize<'de> for IbcTimestamp {
+        fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let nanos = String::deserialize(deserializer)?;
+            nanos
+                .parse()
+                .map(IbcTimestamp)
+                .map_err(serde::de::Error::custom)
+        }
+    }
+
+    /// Heights are ordered by revision first, then by height within the revision.
+    #[derive(
+        Decode, Encode, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord,
+    )]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub struct IbcTimeoutBlock {
         /// the version that the client is currently on
@@ -100,15 +364,136 @@ pub mod ibc {
         pub height: u64,
     }
 
-    #[derive(Decode, Encode)]
+    impl IbcTimeoutBlock {
+        pub fn new(revision: u64, height: u64) -> Self {
+            IbcTimeoutBlock { revision, height }
+        }
+
+        /// A zero height means "no height timeout" in ICS-04.
+        pub fn is_zero(&self) -> bool {
+            self.revision == 0 && self.height == 0
+        }
+    }
+
+    #[derive(Decode, Encode, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub struct IbcAcknowledgement {
-        pub data: Vec<u8>,
+        pub data: Binary,
         // we may add more info here in the future (meta-data from the acknowledgement)
         // there have been proposals to extend this type in core ibc for future versions
     }
 
-    #[derive(Decode, Encode)]
+    impl IbcAcknowledgement {
+        pub fn new(data: impl Into<Binary>) -> Self {
+            IbcAcknowledgement { data: data.into() }
+        }
+
+        /// Parses the acknowledgement written by the counterparty application.
+        pub fn std_ack(&self) -> Result<StdAck, Error> {
+            StdAck::from_json(&self.data)
+        }
+    }
+
+    /// An error that can be reported to the counterparty in an error acknowledgement.
+    ///
+    /// Codes are part of the wire format: relayers and UIs match on them, so a code must
+    /// never change meaning once assigned. `Error` of this crate uses 1-99.
+    pub trait AckError: core::fmt::Display {
+        fn code(&self) -> u32;
+    }
+
+    /// The ICS-04 acknowledgement envelope used by ibc-go applications.
+    /// Serializes to `{"result":"<base64>"}` or `{"error":"<reason>"}`.
+    #[derive(Decode, Encode, Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    #[serde(rename_all = "snake_case")]
+    pub enum StdAck {
+        Result(Binary),
+        Error(String),
+    }
+
+    impl StdAck {
+        /// A successful acknowledgement carrying application specific `data`.
+        pub fn success(data: impl Into<Binary>) -> Self {
+            StdAck::Result(data.into())
+        }
+
+        /// A failed acknowledgement with a human readable reason.
+        pub fn error(reason: impl Into<String>) -> Self {
+            StdAck::Error(reason.into())
+        }
+
+        /// A failed acknowledgement describing an application error, formatted the way
+        /// ibc-go formats redacted errors: `ABCI code: {code}: {message}`.
+        pub fn from_error<E: AckError>(err: &E) -> Self {
+            StdAck::Error(ink::prelude::format!("ABCI code: {}: {}", err.code(), err))
+        }
+
+        pub fn is_success(&self) -> bool {
+            matches!(self, StdAck::Result(_))
+        }
+
+        /// Encodes the acknowledgement exactly like ibc-go's `Acknowledgement.Acknowledgement()`.
+        pub fn to_json(&self) -> Vec<u8> {
+            let mut out = String::from("{");
+            match self {
+                StdAck::Result(data) => {
+                    write_go_json_string(&mut out, "result");
+                    out.push(':');
+                    write_go_json_string(&mut out, &data.to_base64());
+                }
+                StdAck::Error(reason) => {
+                    write_go_json_string(&mut out, "error");
+                    out.push(':');
+                    write_go_json_string(&mut out, reason);
+                }
+            }
+            out.push('}');
+            out.into_bytes()
+        }
+
+        pub fn from_json(data: &[u8]) -> Result<Self, Error> {
+            serde_json::from_slice(data).map_err(|_| Error::ParseError)
+        }
+    }
+
+    impl From<StdAck> for Vec<u8> {
+        fn from(ack: StdAck) -> Self {
+            ack.to_json()
+        }
+    }
+
+    impl From<StdAck> for Binary {
+        fn from(ack: StdAck) -> Self {
+            Binary(ack.to_json())
+        }
+    }
+
+    /// Go marshals `[]byte` as standard, padded base64.
+    mod base64_bytes {
+        use base64::{engine::general_purpose::STANDARD, Engine as _};
+        use ink::prelude::{string::String, vec::Vec};
+        use serde::{de, Deserialize, Deserializer, Serializer};
+
+        pub fn encode(data: &[u8]) -> String {
+            STANDARD.encode(data)
+        }
+
+        pub fn decode(encoded: &str) -> Result<Vec<u8>, base64::DecodeError> {
+            STANDARD.decode(encoded)
+        }
+
+        pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_str(&encode(data))
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+            let encoded = String::deserialize(deserializer)?;
+            decode(&encoded).map_err(de::Error::custom)
+        }
+    }
+
+    #[derive(Decode, Encode, Serialize, Deserialize)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub enum SubMsgResult {
         Ok(SubMsgResponse),
@@ -117,17 +502,18 @@ pub mod ibc {
         Err(String),
     }
 
-    #[derive(Decode, Encode)]
+    #[derive(Decode, Encode, Serialize, Deserialize)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub struct SubMsgResponse {
         pub events: Vec<Event>,
-        pub data: Option<Vec<u8>>,
+        pub data: Option<Binary>,
     }
 
-    #[derive(Decode, Encode)]
+    #[derive(Decode, Encode, Serialize, Deserialize)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub struct Event {
         /// The event type. This is renamed to "ty" because "type" is reserved in Rust. This sucks, we know.
+        #[serde(rename = "type")]
         pub ty: String,
         /// The attributes to be included in the event.
         ///
@@ -137,14 +523,14 @@ pub mod ibc {
         pub attributes: Vec<Attribute>,
     }
 
-    #[derive(Decode, Encode)]
+    #[derive(Decode, Encode, Serialize, Deserialize)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub struct Attribute {
         pub key: String,
         pub value: String,
     }
 
-    #[derive(Decode, Encode, Clone)]
+    #[derive(Decode, Encode, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub struct IbcChannel {
         pub endpoint: IbcEndpoint,
@@ -157,7 +543,7 @@ pub mod ibc {
         pub connection_id: String,
     }
 
-    #[derive(Decode, Encode)]
+    #[derive(Decode, Encode, Serialize, Deserialize)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub enum IbcChannelOpenMsg {
         /// The ChanOpenInit step from https://github.com/cosmos/ibc/tree/master/spec/core/ics-004-channel-and-packet-semantics#channel-lifecycle-management
@@ -169,7 +555,7 @@ pub mod ibc {
         },
     }
 
-    #[derive(Decode, Encode)]
+    #[derive(Decode, Encode, Serialize, Deserialize)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub enum IbcChannelConnectMsg {
         /// The ChanOpenAck step from https://github.com/cosmos/ibc/tree/master/spec/core/ics-004-channel-and-packet-semantics#channel-lifecycle-management
@@ -181,7 +567,7 @@ pub mod ibc {
         OpenConfirm { channel: IbcChannel },
     }
 
-    #[derive(Decode, Encode)]
+    #[derive(Decode, Encode, Serialize, Deserialize)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub enum IbcChannelCloseMsg {
         /// The ChanCloseInit step from https://github.com/cosmos/ibc/tree/master/spec/core/ics-004-channel-and-packet-semantics#channel-lifecycle-management
@@ -190,7 +576,234 @@ pub mod ibc {
         CloseConfirm { channel: IbcChannel }, // pub channel: IbcChannel,
     }
 
-    #[derive(Decode, Encode)]
+    impl IbcChannelOpenMsg {
+        pub fn channel(&self) -> &IbcChannel {
+            match self {
+                Self::OpenInit { channel } => channel,
+                Self::OpenTry { channel, .. } => channel,
+            }
+        }
+
+        pub fn counterparty_version(&self) -> Option<&str> {
+            match self {
+                Self::OpenTry {
+                    counterparty_version,
+                    ..
+                } => Some(counterparty_version),
+                _ => None,
+            }
+        }
+    }
+
+    impl IbcChannelCloseMsg {
+        pub fn channel(&self) -> &IbcChannel {
+            match self {
+                Self::CloseInit { channel } => channel,
+                Self::CloseConfirm { channel } => channel,
+            }
+        }
+    }
+
+    impl IbcChannelConnectMsg {
+        pub fn channel(&self) -> &IbcChannel {
+            match self {
+                Self::OpenAck { channel, .. } => channel,
+                Self::OpenConfirm { channel } => channel,
+            }
+        }
+
+        pub fn counterparty_version(&self) -> Option<&str> {
+            match self {
+                Self::OpenAck {
+                    counterparty_version,
+                    ..
+                } => Some(counterparty_version),
+                _ => None,
+            }
+        }
+    }
+
+    /// Which counterparty ports a channel may be opened with.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub enum PortRule {
+        Any,
+        /// The counterparty port must be exactly this one, eg. `transfer`.
+        Exact(String),
+        /// The counterparty port must start with this, eg. `icacontroller-`.
+        Prefix(String),
+    }
+
+    impl PortRule {
+        pub fn matches(&self, port_id: &str) -> bool {
+            match self {
+                PortRule::Any => true,
+                PortRule::Exact(port) => port_id == port,
+                PortRule::Prefix(prefix) => port_id.starts_with(prefix.as_str()),
+            }
+        }
+    }
+
+    /// The channel handshake rules of an application, checked from `ibc_channel_open`
+    /// and `ibc_channel_connect`.
+    ///
+    /// ```ignore
+    /// let policy = HandshakePolicy::new("ics20-1").with_order(IbcOrder::Unordered);
+    /// let version = policy.validate_open(&msg)?;
+    /// ```
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct HandshakePolicy {
+        /// Accepted versions, the first one is proposed when the relayer leaves it empty.
+        versions: Vec<String>,
+        /// Required channel ordering, any ordering is accepted if unset.
+        order: Option<IbcOrder>,
+        counterparty_port: PortRule,
+        /// Whether the versions may come wrapped by the ICS-29 fee middleware.
+        fees: bool,
+    }
+
+    impl HandshakePolicy {
+        /// Accepts channels of the given version, with any ordering and counterparty port.
+        pub fn new(version: impl Into<String>) -> Self {
+            HandshakePolicy {
+                versions: vec![version.into()],
+                order: None,
+                counterparty_port: PortRule::Any,
+                fees: false,
+            }
+        }
+
+        /// Accepts an additional version, eg. an older revision of the protocol.
+        pub fn allow_version(mut self, version: impl Into<String>) -> Self {
+            self.versions.push(version.into());
+            self
+        }
+
+        pub fn with_order(mut self, order: IbcOrder) -> Self {
+            self.order = Some(order);
+            self
+        }
+
+        pub fn with_counterparty_port(mut self, rule: PortRule) -> Self {
+            self.counterparty_port = rule;
+            self
+        }
+
+        /// Also accepts the versions wrapped in ICS-29 metadata, which opens the channel with
+        /// relayer fees. An empty version on ChanOpenInit still picks the bare version.
+        pub fn with_fees(mut self) -> Self {
+            self.fees = true;
+            self
+        }
+
+        pub fn versions(&self) -> &[String] {
+            &self.versions
+        }
+
+        /// Validates ChanOpenInit/ChanOpenTry and returns the version the channel is opened with.
+        pub fn validate_open(&self, msg: &IbcChannelOpenMsg) -> Result<String, Error> {
+            let channel = msg.channel();
+            self.validate_channel(channel)?;
+            match msg.counterparty_version() {
+                // on OpenTry the counterparty already picked a version, we can only agree
+                Some(counterparty_version) => {
+                    self.check_counterparty_version(counterparty_version)?;
+                    if !channel.version.is_empty() && channel.version != counterparty_version {
+                        return Err(Error::InvalidIbcVersion {
+                            version: channel.version.clone(),
+                        });
+                    }
+                    Ok(counterparty_version.into())
+                }
+                // an empty version on OpenInit lets the application choose
+                None if channel.version.is_empty() => Ok(self.versions[0].clone()),
+                None => {
+                    self.check_version(&channel.version)?;
+                    Ok(channel.version.clone())
+                }
+            }
+        }
+
+        /// Validates ChanOpenInit/ChanOpenTry and builds the `ibc_channel_open` response.
+        /// With `ibc3` the negotiated version is handed back to the relayer, which is how
+        /// an empty version proposed on ChanOpenInit gets filled in.
+        pub fn open_response(&self, msg: &IbcChannelOpenMsg) -> Result<IbcChannelOpenResponse, Error> {
+            let version = self.validate_open(msg)?;
+            #[cfg(feature = "ibc3")]
+            return Ok(Some(Ibc3ChannelOpenResponse { version }));
+            #[cfg(not(feature = "ibc3"))]
+            {
+                let _ = version;
+                Ok(())
+            }
+        }
+
+        /// Validates ChanOpenAck/ChanOpenConfirm and returns the negotiated version.
+        pub fn validate_connect(&self, msg: &IbcChannelConnectMsg) -> Result<String, Error> {
+            let channel = msg.channel();
+            self.validate_channel(channel)?;
+            self.check_version(&channel.version)?;
+            if let Some(counterparty_version) = msg.counterparty_version() {
+                self.check_counterparty_version(counterparty_version)?;
+                if counterparty_version != channel.version {
+                    return Err(Error::InvalidVersion {
+                        actual: counterparty_version.into(),
+                        expected: channel.version.clone(),
+                    });
+                }
+            }
+            Ok(channel.version.clone())
+        }
+
+        fn validate_channel(&self, channel: &IbcChannel) -> Result<(), Error> {
+            match (&self.order, &channel.order) {
+                (Some(IbcOrder::Ordered), IbcOrder::Unordered) => {
+                    return Err(Error::OnlyOrderedChannel)
+                }
+                (Some(IbcOrder::Unordered), IbcOrder::Ordered) => return Err(Error::OrderedChannel),
+                _ => {}
+            }
+            let port_id = &channel.counterparty_endpoint.port_id;
+            if !self.counterparty_port.matches(port_id) {
+                return Err(Error::InvalidCounterpartyPort {
+                    port: port_id.clone(),
+                });
+            }
+            Ok(())
+        }
+
+        fn is_supported(&self, version: &str) -> bool {
+            if self.fees {
+                if let Some(fee) = FeeVersion::parse(version) {
+                    return fee.fee_version == FEE_VERSION
+                        && self.versions.contains(&fee.app_version);
+                }
+            }
+            self.versions.iter().any(|v| v == version)
+        }
+
+        fn check_version(&self, version: &str) -> Result<(), Error> {
+            if self.is_supported(version) {
+                Ok(())
+            } else {
+                Err(Error::InvalidIbcVersion {
+                    version: version.into(),
+                })
+            }
+        }
+
+        fn check_counterparty_version(&self, version: &str) -> Result<(), Error> {
+            if self.is_supported(version) {
+                Ok(())
+            } else {
+                Err(Error::InvalidVersion {
+                    actual: version.into(),
+                    expected: self.versions[0].clone(),
+                })
+            }
+        }
+    }
+
+    #[derive(Decode, Encode, Serialize, Deserialize)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub struct IbcPacketReceiveMsg {
         pub packet: IbcPacket,
@@ -198,7 +811,19 @@ pub mod ibc {
         pub relayer: Addr,
     }
 
-    #[derive(Decode, Encode)]
+    impl IbcPacketReceiveMsg {
+        #[cfg(not(feature = "ibc3"))]
+        pub fn new(packet: IbcPacket) -> Self {
+            Self { packet }
+        }
+
+        #[cfg(feature = "ibc3")]
+        pub fn new(packet: IbcPacket, relayer: Addr) -> Self {
+            Self { packet, relayer }
+        }
+    }
+
+    #[derive(Decode, Encode, Serialize, Deserialize)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub struct IbcPacketAckMsg {
         pub acknowledgement: IbcAcknowledgement,
@@ -207,7 +832,30 @@ pub mod ibc {
         pub relayer: Addr,
     }
 
-    #[derive(Decode, Encode)]
+    impl IbcPacketAckMsg {
+        #[cfg(not(feature = "ibc3"))]
+        pub fn new(acknowledgement: IbcAcknowledgement, original_packet: IbcPacket) -> Self {
+            Self {
+                acknowledgement,
+                original_packet,
+            }
+        }
+
+        #[cfg(feature = "ibc3")]
+        pub fn new(
+            acknowledgement: IbcAcknowledgement,
+            original_packet: IbcPacket,
+            relayer: Addr,
+        ) -> Self {
+            Self {
+                acknowledgement,
+                original_packet,
+                relayer,
+            }
+        }
+    }
+
+    #[derive(Decode, Encode, Serialize, Deserialize)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub struct IbcPacketTimeoutMsg {
         pub packet: IbcPacket,
@@ -215,10 +863,23 @@ pub mod ibc {
         pub relayer: Addr,
     }
 
-    #[derive(Decode, Encode, Serialize, Deserialize)]
-    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
-    pub enum CosmosMsg<T> {
-        Bank(BankMsg),
+    impl IbcPacketTimeoutMsg {
+        #[cfg(not(feature = "ibc3"))]
+        pub fn new(packet: IbcPacket) -> Self {
+            Self { packet }
+        }
+
+        #[cfg(feature = "ibc3")]
+        pub fn new(packet: IbcPacket, relayer: Addr) -> Self {
+            Self { packet, relayer }
+        }
+    }
+
+    #[derive(Decode, Encode, Serialize, Deserialize)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    #[serde(rename_all = "snake_case")]
+    pub enum CosmosMsg<T> {
+        Bank(BankMsg),
         // by default we use RawMsg, but a contract can override that
         // to call into more app-specific code (whatever they define)
         Custom(T),
@@ -231,7 +892,7 @@ pub mod ibc {
         //#[cfg(feature = "stargate")]
         Stargate {
             type_url: String,
-            value: Vec<u8>,
+            value: Binary,
         },
         //#[cfg(feature = "stargate")]
         Ibc(IbcMsg),
@@ -252,8 +913,15 @@ pub mod ibc {
         }
     }
 
-    #[derive(Decode, Encode, Serialize, Deserialize)]
+    impl<T> From<IbcMsg> for CosmosMsg<T> {
+        fn from(msg: IbcMsg) -> Self {
+            CosmosMsg::Ibc(msg)
+        }
+    }
+
+    #[derive(Decode, Encode, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    #[serde(rename_all = "snake_case")]
     pub enum BankMsg {
         /// Sends native tokens from the contract to the given address.
         ///
@@ -267,10 +935,18 @@ pub mod ibc {
         /// There is no Cosmos SDK message that performs this, but it can be done by calling the bank keeper.
         /// Important if a contract controls significant token supply that must be retired.
         Burn { amount: Vec<Coin> },
+        /// Mints new tokens of the given denoms to the given address, e.g. ICS-20 vouchers
+        /// for tokens received from another chain. Only the IBC apps the runtime trusts
+        /// with minting may emit this.
+        Mint {
+            to_address: String,
+            amount: Vec<Coin>,
+        },
     }
 
     #[derive(Decode, Encode, Serialize, Deserialize)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    #[serde(rename_all = "snake_case")]
     pub enum StakingMsg {
         /// This is translated to a [MsgDelegate](https://github.com/cosmos/cosmos-sdk/blob/v0.40.0/proto/cosmos/staking/v1beta1/tx.proto#L81-L90).
         /// `delegator_address` is automatically filled with the current contract's address.
@@ -289,6 +965,7 @@ pub mod ibc {
 
     #[derive(Decode, Encode, Serialize, Deserialize)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    #[serde(rename_all = "snake_case")]
     pub enum DistributionMsg {
         /// This is translated to a [MsgSetWithdrawAddress](https://github.com/cosmos/cosmos-sdk/blob/v0.42.4/proto/cosmos/distribution/v1beta1/tx.proto#L29-L37).
         /// `delegator_address` is automatically filled with the current contract's address.
@@ -306,6 +983,7 @@ pub mod ibc {
 
     #[derive(Decode, Encode, Serialize, Deserialize)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    #[serde(rename_all = "snake_case")]
     pub enum WasmMsg {
         /// Dispatches a call to another contract at a known address (with known ABI).
         ///
@@ -315,7 +993,7 @@ pub mod ibc {
             contract_addr: String,
             /// msg is the json-encoded ExecuteMsg struct (as raw Binary)
             //#[derivative(Debug(format_with = "binary_to_string"))]
-            msg: Vec<u8>,
+            msg: Binary,
             funds: Vec<Coin>,
         },
         /// Instantiates a new contracts from previously uploaded Wasm code.
@@ -332,7 +1010,7 @@ pub mod ibc {
             code_id: u64,
             /// msg is the JSON-encoded InstantiateMsg struct (as raw Binary)
             //#[derivative(Debug(format_with = "binary_to_string"))]
-            msg: Vec<u8>,
+            msg: Binary,
             funds: Vec<Coin>,
             /// A human-readbale label for the contract
             label: String,
@@ -350,7 +1028,7 @@ pub mod ibc {
             new_code_id: u64,
             /// msg is the json-encoded MigrateMsg struct that will be passed to the new code
             //#[derivative(Debug(format_with = "binary_to_string"))]
-            msg: Vec<u8>,
+            msg: Binary,
         },
         /// Sets a new admin (for migrate) on the given contract.
         /// Fails if this contract is not currently admin of the target contract.
@@ -365,6 +1043,7 @@ pub mod ibc {
 
     #[derive(Decode, Encode, Serialize, Deserialize)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    #[serde(rename_all = "snake_case")]
     pub enum IbcMsg {
         /// Sends bank tokens owned by the contract to the given address on another chain.
         /// The channel must already be established between the ibctransfer module on this chain
@@ -387,17 +1066,25 @@ pub mod ibc {
         /// and the module on the other side should know how to parse this.
         SendPacket {
             channel_id: String,
-            data: Vec<u8>,
+            data: Binary,
             /// when packet times out, measured on remote chain
             timeout: IbcTimeout,
         },
         /// This will close an existing channel that is owned by this contract.
         /// Port is auto-assigned to the contract's IBC port
         CloseChannel { channel_id: String },
+        /// Acknowledges a packet this contract received without acknowledgement,
+        /// see `IbcReceiveResponse::without_ack`.
+        WriteAcknowledgement {
+            /// our channel the packet was received on
+            channel_id: String,
+            packet_sequence: u64,
+            ack: IbcAcknowledgement,
+        },
     }
 
-    #[derive(Decode, Encode, Serialize, Deserialize)]
-    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    #[derive(Decode, Encode, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(StorageLayout, scale_info::TypeInfo))]
     pub struct Coin {
         pub denom: String,
         pub amount: u128,
@@ -407,7 +1094,7 @@ pub mod ibc {
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub struct Empty {}
 
-    #[derive(Decode, Encode)]
+    #[derive(Decode, Encode, Serialize, Deserialize)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub struct Response<T = Empty> {
         /// Optional list of messages to pass. These will be executed in order.
@@ -430,7 +1117,7 @@ pub mod ibc {
         /// [*Cosmos SDK* docs]: https://docs.cosmos.network/main/core/events.html
         pub events: Vec<Event>,
         /// The binary payload to include in the response.
-        pub data: Option<Vec<u8>>,
+        pub data: Option<Binary>,
     }
 
     impl<T> Default for Response<T> {
@@ -578,7 +1265,7 @@ pub mod ibc {
         }
 
         /// Set the binary data included in the response.
-        pub fn set_data(mut self, data: impl Into<Vec<u8>>) -> Self {
+        pub fn set_data(mut self, data: impl Into<Binary>) -> Self {
             self.data = Some(data.into());
             self
         }
@@ -690,7 +1377,7 @@ pub mod ibc {
         // Wasm(WasmQuery),
     }
 
-    #[derive(Decode, Encode)]
+    #[derive(Decode, Encode, Serialize, Deserialize)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub struct Reply {
         /// The ID that the contract set when emitting the `SubMsg`.
@@ -700,24 +1387,22 @@ pub mod ibc {
     }
 
     /// Note that this serializes as "null".
-
     #[cfg(not(feature = "ibc3"))]
     pub type IbcChannelOpenResponse = ();
 
     /// This serializes either as "null" or a JSON object.
-    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    /// `None` accepts the version proposed by the counterparty.
     #[cfg(feature = "ibc3")]
-    #[derive(Decode, Encode)]
     pub type IbcChannelOpenResponse = Option<Ibc3ChannelOpenResponse>;
 
-    #[derive(Decode, Encode)]
+    #[derive(Decode, Encode, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub struct Ibc3ChannelOpenResponse {
         /// We can set the channel version to a different one than we were called with
         pub version: String,
     }
 
-    #[derive(Decode, Encode)]
+    #[derive(Decode, Encode, Serialize, Deserialize)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub struct IbcBasicResponse<T = Empty> {
         /// Optional list of messages to pass. These will be executed in order.
@@ -860,12 +1545,12 @@ pub mod ibc {
         }
     }
 
-    #[derive(Decode, Encode)]
+    #[derive(Decode, Encode, Serialize, Deserialize)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub struct IbcReceiveResponse<T = Empty> {
         /// The bytes we return to the contract that sent the packet.
         /// This may represent a success or error of exection
-        pub acknowledgement: Vec<u8>,
+        pub acknowledgement: Binary,
         /// Optional list of messages to pass. These will be executed in order.
         /// If the ReplyOn member is set, they will invoke this contract's `reply` entry point
         /// after execution. Otherwise, they act like "fire and forget".
@@ -889,7 +1574,7 @@ pub mod ibc {
     impl<T> Default for IbcReceiveResponse<T> {
         fn default() -> Self {
             IbcReceiveResponse {
-                acknowledgement: vec![],
+                acknowledgement: Binary::default(),
                 messages: vec![],
                 attributes: vec![],
                 events: vec![],
@@ -902,8 +1587,19 @@ pub mod ibc {
             Self::default()
         }
 
+        /// A response that leaves the packet unacknowledged for now, like an empty ack in
+        /// ibc-go. The contract writes the ack later with `IbcMsg::WriteAcknowledgement`.
+        pub fn without_ack() -> Self {
+            Self::default()
+        }
+
+        /// Whether the acknowledgement is written later.
+        pub fn is_async(&self) -> bool {
+            self.acknowledgement.is_empty()
+        }
+
         /// Set the acknowledgement for this response.
-        pub fn set_ack(mut self, ack: impl Into<Vec<u8>>) -> Self {
+        pub fn set_ack(mut self, ack: impl Into<Binary>) -> Self {
             self.acknowledgement = ack.into();
             self
         }
@@ -1015,7 +1711,8 @@ pub mod ibc {
     #[derive(Encode, Decode, Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub struct Ics20Packet {
-        /// amount of tokens to transfer is encoded as a string, but limited to u64 max
+        /// amount of tokens to transfer is encoded as a string, but limited to u128 max
+        #[serde(with = "amount_string")]
         pub amount: u128,
         /// the token denomination to be transferred
         pub denom: String,
@@ -1024,29 +1721,1024 @@ pub mod ibc {
         /// the sender address
         pub sender: String,
         /// optional memo for the IBC transfer
-        //#[serde(skip_serializing_if = "Option::is_none")]
+        #[serde(default, skip_serializing_if = "Option::is_none")]
         pub memo: Option<String>,
     }
 
+    impl Ics20Packet {
+        pub fn new(
+            amount: u128,
+            denom: impl Into<String>,
+            sender: impl Into<String>,
+            receiver: impl Into<String>,
+            memo: Option<String>,
+        ) -> Self {
+            Ics20Packet {
+                amount,
+                denom: denom.into(),
+                receiver: receiver.into(),
+                sender: sender.into(),
+                memo,
+            }
+        }
+
+        /// Encodes the packet the way ibc-go does (`FungibleTokenPacketData.GetBytes`):
+        /// proto3 JSON without default values, keys sorted, Go `encoding/json` escaping.
+        ///
+        /// These are the bytes the packet commitment is computed over, so they have to be
+        /// identical to what a Cosmos counterparty produces for the same packet.
+        pub fn to_json(&self) -> Vec<u8> {
+            let mut out = String::from("{");
+            let amount = self.amount.to_string();
+            let memo = self.memo.as_deref().unwrap_or_default();
+            let fields = [
+                ("amount", amount.as_str()),
+                ("denom", self.denom.as_str()),
+                ("memo", memo),
+                ("receiver", self.receiver.as_str()),
+                ("sender", self.sender.as_str()),
+            ];
+            for (key, value) in fields.iter().filter(|(_, value)| !value.is_empty()) {
+                if out.len() > 1 {
+                    out.push(',');
+                }
+                write_go_json_string(&mut out, key);
+                out.push(':');
+                write_go_json_string(&mut out, value);
+            }
+            out.push('}');
+            out.into_bytes()
+        }
+
+        /// Decodes ICS-20 packet data as sent by ibc-go. An empty memo is treated as no memo,
+        /// so `from_json(p.to_json()) == p` holds for every packet we emit.
+        pub fn from_json(data: &[u8]) -> Result<Self, Error> {
+            let mut packet: Ics20Packet =
+                serde_json::from_slice(data).map_err(|_| Error::ParseError)?;
+            if packet.memo.as_deref() == Some("") {
+                packet.memo = None;
+            }
+            Ok(packet)
+        }
+    }
+
+    /// A contract call requested by the `ink` key of an ICS-20 memo, our counterpart of the
+    /// `wasm` key of ibc-hooks:
+    /// `{"ink":{"contract":"0x..","selector":"0x12345678","args":"<base64 SCALE args>"}}`.
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+    pub struct InkHook {
+        /// the contract to call, it must also be the receiver of the transfer
+        pub contract: String,
+        /// selector of the message, as `0x` prefixed hex
+        #[serde(with = "selector_hex")]
+        pub selector: [u8; 4],
+        /// the SCALE encoded arguments of the message
+        #[serde(default)]
+        pub args: Binary,
+    }
+
+    impl InkHook {
+        /// Reads the hook out of a transfer memo. Memos that are not JSON objects or lack the
+        /// `ink` key are no hooks, a malformed `ink` value is a `ParseError`.
+        pub fn from_memo(memo: &str) -> Result<Option<Self>, Error> {
+            match memo_entry(memo, "ink") {
+                Some(hook) => serde_json::from_value(hook)
+                    .map(Some)
+                    .map_err(|_| Error::ParseError),
+                None => Ok(None),
+            }
+        }
+
+        /// The input of the call: the selector followed by the arguments.
+        pub fn call_data(&self) -> Vec<u8> {
+            let mut data = self.selector.to_vec();
+            data.extend_from_slice(&self.args);
+            data
+        }
+    }
+
+    /// The value of `key` if `memo` is a JSON object, the way middlewares share the memo.
+    fn memo_entry(memo: &str, key: &str) -> Option<serde_json::Value> {
+        match serde_json::from_str::<serde_json::Value>(memo) {
+            Ok(serde_json::Value::Object(mut memo)) => memo.remove(key),
+            _ => None,
+        }
+    }
+
+    /// The `forward` key of an ICS-20 memo, as defined by the packet-forward middleware:
+    /// `{"forward":{"receiver":"..","port":"transfer","channel":"channel-1","timeout":600000000000,"retries":2,"next":{..}}}`.
+    #[derive(Decode, Encode, Debug, PartialEq, Eq, Clone)]
+    #[cfg_attr(feature = "std", derive(StorageLayout, scale_info::TypeInfo))]
+    pub struct ForwardMetadata {
+        /// receiver on the next chain
+        pub receiver: String,
+        /// our port and channel to forward over
+        pub port: String,
+        pub channel: String,
+        /// relative timeout of the forwarded packet in nanoseconds, the app's default if unset
+        pub timeout: Option<u64>,
+        /// how often the forward is resent after timing out
+        pub retries: Option<u8>,
+        /// memo of the forwarded packet, so routes can span several hops
+        pub next: Option<String>,
+    }
+
+    impl ForwardMetadata {
+        /// Reads the forward instructions out of a transfer memo, `None` if there are none.
+        /// A malformed `forward` value is a `ParseError`.
+        pub fn from_memo(memo: &str) -> Result<Option<Self>, Error> {
+            #[derive(Deserialize)]
+            struct Forward {
+                receiver: String,
+                port: String,
+                channel: String,
+                #[serde(default)]
+                timeout: Option<u64>,
+                #[serde(default)]
+                retries: Option<u8>,
+                #[serde(default)]
+                next: Option<serde_json::Value>,
+            }
+
+            let forward = match memo_entry(memo, "forward") {
+                Some(forward) => forward,
+                None => return Ok(None),
+            };
+            let forward: Forward = serde_json::from_value(forward).map_err(|_| Error::ParseError)?;
+            // pfm accepts the next memo as an object or as a string holding one
+            let next = match forward.next {
+                Some(serde_json::Value::String(next)) => Some(next),
+                Some(next) => Some(next.to_string()),
+                None => None,
+            };
+            Ok(Some(ForwardMetadata {
+                receiver: forward.receiver,
+                port: forward.port,
+                channel: forward.channel,
+                timeout: forward.timeout,
+                retries: forward.retries,
+                next,
+            }))
+        }
+    }
+
+    mod selector_hex {
+        use ink::prelude::string::String;
+        use serde::{de, Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(selector: &[u8; 4], serializer: S) -> Result<S::Ok, S::Error> {
+            let hex = u32::from_be_bytes(*selector);
+            serializer.serialize_str(&ink::prelude::format!("0x{:08x}", hex))
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 4], D::Error> {
+            let selector = String::deserialize(deserializer)?;
+            let hex = selector.strip_prefix("0x").unwrap_or(&selector);
+            if hex.len() != 8 {
+                return Err(de::Error::custom("selector must be 4 hex encoded bytes"));
+            }
+            u32::from_str_radix(hex, 16)
+                .map(u32::to_be_bytes)
+                .map_err(de::Error::custom)
+        }
+    }
+
+    /// ICS-20 carries amounts as decimal strings (`sdk.Int`), never as JSON numbers.
+    mod amount_string {
+        use ink::prelude::string::{String, ToString};
+        use serde::{de, Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(amount: &u128, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_str(&amount.to_string())
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u128, D::Error> {
+            let amount = String::deserialize(deserializer)?;
+            if amount.is_empty() || !amount.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(de::Error::custom("amount must be a decimal string"));
+            }
+            amount.parse().map_err(de::Error::custom)
+        }
+    }
+
+    /// Writes `value` as a JSON string literal, escaped exactly like Go's `encoding/json`
+    /// (which ibc-go runs every packet through via `sdk.MustSortJSON`).
+    fn write_go_json_string(out: &mut String, value: &str) {
+        const HEX: &[u8; 16] = b"0123456789abcdef";
+        out.push('"');
+        for c in value.chars() {
+            match c {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\t' => out.push_str("\\t"),
+                // control characters plus the HTML-sensitive ones Go escapes by default
+                '\u{0}'..='\u{1f}' | '<' | '>' | '&' | '\u{2028}' | '\u{2029}' => {
+                    let code = c as u32;
+                    out.push_str("\\u");
+                    for shift in [12, 8, 4, 0] {
+                        out.push(HEX[((code >> shift) & 0xf) as usize] as char);
+                    }
+                }
+                c => out.push(c),
+            }
+        }
+        out.push('"');
+    }
+
+    /// Prefix of hashed voucher denominations, `ibc/{SHA256(full path)}`.
+    pub const IBC_DENOM_PREFIX: &str = "ibc/";
+
+    /// The trace of a fungible token, following ICS-20 / ibc-go `DenomTrace`.
+    ///
+    /// `path` is the chain of `port/channel` hops the token went through, most recent hop
+    /// first, e.g. `transfer/channel-1/transfer/channel-7`; it is empty for native tokens.
+    #[derive(Decode, Encode, Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+    #[cfg_attr(feature = "std", derive(StorageLayout, scale_info::TypeInfo))]
+    pub struct DenomTrace {
+        pub path: String,
+        pub base_denom: String,
+    }
+
+    impl DenomTrace {
+        /// Parses a full denomination path like `transfer/channel-0/uatom`.
+        ///
+        /// Like ibc-go, only leading `{port}/channel-{N}` pairs count as hops; everything
+        /// after the first pair that is not one belongs to the base denom, so base denoms
+        /// may contain slashes themselves (`transfer/channel-0/gamm/pool/1`).
+        pub fn parse(full_denom: &str) -> Self {
+            let items: Vec<&str> = full_denom.split('/').collect();
+            let mut hops = 0;
+            while hops * 2 + 2 < items.len() && is_channel_id(items[hops * 2 + 1]) {
+                hops += 1;
+            }
+            DenomTrace {
+                path: items[..hops * 2].join("/"),
+                base_denom: items[hops * 2..].join("/"),
+            }
+        }
+
+        /// `true` if the token has not been transferred over IBC, i.e. it is native here.
+        pub fn is_native(&self) -> bool {
+            self.path.is_empty()
+        }
+
+        /// The full denomination path, `{path}/{base_denom}`, that travels in ICS-20 packets.
+        pub fn full_path(&self) -> String {
+            if self.path.is_empty() {
+                self.base_denom.clone()
+            } else {
+                [self.path.as_str(), self.base_denom.as_str()].join("/")
+            }
+        }
+
+        /// SHA256 of the full denomination path.
+        pub fn hash(&self) -> [u8; 32] {
+            let mut output = [0u8; 32];
+            ink::env::hash_bytes::<ink::env::hash::Sha2x256>(
+                self.full_path().as_bytes(),
+                &mut output,
+            );
+            output
+        }
+
+        /// The local denomination: the base denom for native tokens, `ibc/{HASH}` for vouchers.
+        pub fn ibc_denom(&self) -> String {
+            if self.is_native() {
+                return self.base_denom.clone();
+            }
+            let mut denom = String::from(IBC_DENOM_PREFIX);
+            denom.push_str(&hex_upper(&self.hash()));
+            denom
+        }
+
+        /// Returns the trace after one more hop, received on `port_id/channel_id`.
+        pub fn add_prefix(&self, port_id: &str, channel_id: &str) -> Self {
+            let mut path = denom_prefix(port_id, channel_id);
+            path.push_str(&self.path);
+            DenomTrace {
+                path: path.trim_end_matches('/').into(),
+                base_denom: self.base_denom.clone(),
+            }
+        }
+
+        /// Unwinds the most recent hop if it went through `port_id/channel_id`,
+        /// i.e. the token is returning to the chain it came from.
+        pub fn remove_prefix(&self, port_id: &str, channel_id: &str) -> Option<Self> {
+            let hop = [port_id, channel_id].join("/");
+            let path = if self.path == hop {
+                ""
+            } else {
+                self.path.strip_prefix(&denom_prefix(port_id, channel_id))?
+            };
+            Some(DenomTrace {
+                path: path.into(),
+                base_denom: self.base_denom.clone(),
+            })
+        }
+    }
+
+    /// `{port_id}/{channel_id}/`, the prefix a hop adds to a denomination.
+    pub fn denom_prefix(port_id: &str, channel_id: &str) -> String {
+        let mut prefix = [port_id, channel_id].join("/");
+        prefix.push('/');
+        prefix
+    }
+
+    /// `true` if the tokens in a packet sent from `source_port/source_channel` return to their
+    /// origin on the receiving chain, i.e. the receiver unescrows instead of minting vouchers.
+    pub fn receiver_chain_is_source(source_port: &str, source_channel: &str, denom: &str) -> bool {
+        denom.starts_with(&denom_prefix(source_port, source_channel))
+    }
+
+    /// `true` if the sending chain is the source of `denom`, i.e. the sender escrows the
+    /// tokens instead of burning vouchers.
+    pub fn sender_chain_is_source(source_port: &str, source_channel: &str, denom: &str) -> bool {
+        !receiver_chain_is_source(source_port, source_channel, denom)
+    }
+
+    /// Parses the hash out of an `ibc/{HASH}` denomination. Returns `None` for anything else.
+    pub fn parse_ibc_denom_hash(denom: &str) -> Option<[u8; 32]> {
+        let hex = denom.strip_prefix(IBC_DENOM_PREFIX)?.as_bytes();
+        if hex.len() != 64 {
+            return None;
+        }
+        let mut hash = [0u8; 32];
+        for (byte, pair) in hash.iter_mut().zip(hex.chunks(2)) {
+            let digit = |c: u8| (c as char).to_digit(16);
+            *byte = (digit(pair[0])? << 4 | digit(pair[1])?) as u8;
+        }
+        Some(hash)
+    }
+
+    /// `channel-{N}` as generated by ibc-go's channel keeper.
+    fn is_channel_id(id: &str) -> bool {
+        match id.strip_prefix("channel-") {
+            Some(n) => !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()),
+            None => false,
+        }
+    }
+
+    fn hex_upper(bytes: &[u8]) -> String {
+        const HEX: &[u8; 16] = b"0123456789ABCDEF";
+        bytes
+            .iter()
+            .flat_map(|b| [HEX[(b >> 4) as usize] as char, HEX[(b & 0xf) as usize] as char])
+            .collect()
+    }
+
+    /// Persists every trace a contract has seen, so `ibc/{HASH}` denoms can be resolved back
+    /// to the path that has to go into outgoing packets.
+    #[ink::storage_item]
+    #[derive(Default, Debug)]
+    pub struct DenomTraces {
+        traces: ink::storage::Mapping<[u8; 32], DenomTrace>,
+    }
+
+    impl DenomTraces {
+        /// Records `trace` and returns its local denomination.
+        pub fn insert(&mut self, trace: &DenomTrace) -> String {
+            if !trace.is_native() {
+                self.traces.insert(trace.hash(), trace);
+            }
+            trace.ibc_denom()
+        }
+
+        pub fn get(&self, hash: &[u8; 32]) -> Option<DenomTrace> {
+            self.traces.get(hash)
+        }
+
+        /// Resolves a local denomination: `ibc/{HASH}` is looked up, anything else is native.
+        pub fn resolve(&self, denom: &str) -> Option<DenomTrace> {
+            if denom.starts_with(IBC_DENOM_PREFIX) {
+                return self.get(&parse_ibc_denom_hash(denom)?);
+            }
+            Some(DenomTrace {
+                path: String::new(),
+                base_denom: denom.into(),
+            })
+        }
+    }
+
+    /// The sequences a channel is at: the next packet sent, received and acknowledged.
+    #[derive(Decode, Encode, Clone, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(StorageLayout, scale_info::TypeInfo))]
+    pub struct ChannelSequences {
+        pub order: IbcOrder,
+        pub next_send: u64,
+        pub next_recv: u64,
+        pub next_ack: u64,
+        /// set once a packet timed out on an ordered channel, which closes it
+        pub closed: bool,
+    }
+
+    impl ChannelSequences {
+        pub fn new(order: IbcOrder) -> Self {
+            // ICS-04 sequences start at 1
+            ChannelSequences {
+                order,
+                next_send: 1,
+                next_recv: 1,
+                next_ack: 1,
+                closed: false,
+            }
+        }
+    }
+
+    /// Tracks the sequences of every channel a contract is bound to, so ordered channels
+    /// only take packets and acks in sequence.
+    ///
+    /// Channels connected before tracking started are treated as unordered: their next
+    /// sequences follow the highest one seen.
+    #[ink::storage_item]
+    #[derive(Default, Debug)]
+    pub struct SequenceTracker {
+        channels: ink::storage::Mapping<String, ChannelSequences>,
+    }
+
+    impl SequenceTracker {
+        /// Starts tracking a channel, from `ibc_channel_connect`.
+        pub fn open(&mut self, msg: &IbcChannelConnectMsg) {
+            let channel = msg.channel();
+            let sequences = ChannelSequences::new(channel.order.clone());
+            self.channels.insert(&channel.endpoint.channel_id, &sequences);
+        }
+
+        pub fn get(&self, channel_id: &str) -> Option<ChannelSequences> {
+            self.channels.get(channel_id)
+        }
+
+        /// Records a packet the contract sent, with the sequence the chain gave it.
+        pub fn on_send(&mut self, channel_id: &str, sequence: u64) {
+            let mut sequences = self.load(channel_id);
+            sequences.next_send = sequences.next_send.max(sequence.saturating_add(1));
+            self.channels.insert(channel_id, &sequences);
+        }
+
+        /// Checks and records a packet delivered to `ibc_packet_receive`. On ordered channels
+        /// anything but the next packet is rejected.
+        pub fn on_receive(&mut self, packet: &IbcPacket) -> Result<(), Error> {
+            let channel_id = &packet.dest.channel_id;
+            let mut sequences = self.load_open(channel_id)?;
+            sequences.next_recv = next_in_order(
+                channel_id,
+                &sequences.order,
+                sequences.next_recv,
+                packet.sequence,
+            )?;
+            self.channels.insert(channel_id, &sequences);
+            Ok(())
+        }
+
+        /// Checks and records the acknowledgement of a packet we sent. On ordered channels
+        /// acks arrive in sequence.
+        pub fn on_ack(&mut self, packet: &IbcPacket) -> Result<(), Error> {
+            self.settle(packet).map(|_| ())
+        }
+
+        /// Records a timed out packet we sent. This closes an ordered channel, nothing is
+        /// delivered on it afterwards.
+        pub fn on_timeout(&mut self, packet: &IbcPacket) -> Result<(), Error> {
+            let mut sequences = self.settle(packet)?;
+            if sequences.order == IbcOrder::Ordered {
+                sequences.closed = true;
+                self.channels.insert(&packet.src.channel_id, &sequences);
+            }
+            Ok(())
+        }
+
+        fn settle(&mut self, packet: &IbcPacket) -> Result<ChannelSequences, Error> {
+            let channel_id = &packet.src.channel_id;
+            let mut sequences = self.load_open(channel_id)?;
+            sequences.next_ack = next_in_order(
+                channel_id,
+                &sequences.order,
+                sequences.next_ack,
+                packet.sequence,
+            )?;
+            self.channels.insert(channel_id, &sequences);
+            Ok(sequences)
+        }
+
+        fn load_open(&self, channel_id: &str) -> Result<ChannelSequences, Error> {
+            let sequences = self.load(channel_id);
+            if sequences.closed {
+                return Err(Error::ChannelClosed {
+                    channel: channel_id.into(),
+                });
+            }
+            Ok(sequences)
+        }
+
+        fn load(&self, channel_id: &str) -> ChannelSequences {
+            match self.channels.get(channel_id) {
+                Some(sequences) => sequences,
+                None => ChannelSequences::new(IbcOrder::Unordered),
+            }
+        }
+    }
+
+    /// The sequence expected after `sequence`, which has to be `expected` on ordered channels.
+    fn next_in_order(
+        channel_id: &str,
+        order: &IbcOrder,
+        expected: u64,
+        sequence: u64,
+    ) -> Result<u64, Error> {
+        match order {
+            IbcOrder::Ordered if sequence != expected => Err(Error::PacketOutOfOrder {
+                channel: channel_id.into(),
+                expected,
+                actual: sequence,
+            }),
+            _ => Ok(expected.max(sequence.saturating_add(1))),
+        }
+    }
+
+    /// The fee version ICS-29 negotiates next to the application version.
+    pub const FEE_VERSION: &str = "ics29-1";
+
+    /// The version of a fee enabled channel, the application version wrapped by the fee
+    /// middleware: `{"fee_version":"ics29-1","app_version":"ics20-1"}`.
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+    pub struct FeeVersion {
+        pub fee_version: String,
+        pub app_version: String,
+    }
+
+    impl FeeVersion {
+        pub fn new(app_version: impl Into<String>) -> Self {
+            FeeVersion {
+                fee_version: FEE_VERSION.into(),
+                app_version: app_version.into(),
+            }
+        }
+
+        /// Reads the metadata out of a channel version, `None` for a bare application version.
+        pub fn parse(version: &str) -> Option<Self> {
+            serde_json::from_str(version).ok()
+        }
+
+        /// The channel version, as ibc-go renders it.
+        pub fn to_version(&self) -> String {
+            serde_json::to_string(self).unwrap_or_default()
+        }
+    }
+
+    /// The fees a packet pays its relayers, each in any number of denoms.
+    #[derive(Decode, Encode, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(StorageLayout, scale_info::TypeInfo))]
+    pub struct Fee {
+        /// paid to the relayer that delivered the packet to the counterparty
+        pub recv_fee: Vec<Coin>,
+        /// paid to the relayer that brought the acknowledgement back
+        pub ack_fee: Vec<Coin>,
+        /// paid to the relayer that proved the timeout, instead of the other two
+        pub timeout_fee: Vec<Coin>,
+    }
+
+    impl Fee {
+        /// What has to be escrowed for the fee: the timeout fee or the recv and ack fees
+        /// are paid, so the larger of the two per denom.
+        pub fn total(&self) -> Vec<Coin> {
+            let mut total = Vec::new();
+            add_coins(&mut total, &self.recv_fee);
+            add_coins(&mut total, &self.ack_fee);
+            for coin in &self.timeout_fee {
+                match total.iter_mut().find(|c| c.denom == coin.denom) {
+                    Some(c) => c.amount = c.amount.max(coin.amount),
+                    None => total.push(coin.clone()),
+                }
+            }
+            total
+        }
+    }
+
+    /// A fee escrowed for a packet, and who gets back what the relayers are not paid.
+    #[derive(Decode, Encode, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(StorageLayout, scale_info::TypeInfo))]
+    pub struct PacketFee {
+        pub fee: Fee,
+        pub refund_address: Addr,
+    }
+
+    pub type PacketFees = Vec<PacketFee>;
+
+    /// The acknowledgement on a fee enabled channel: the application's ack, plus where the
+    /// receiving relayer wants its recv fee paid on the sending chain.
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+    pub struct IncentivizedAck {
+        pub app_acknowledgement: Binary,
+        pub forward_relayer_address: String,
+        pub underlying_app_success: bool,
+    }
+
+    impl IncentivizedAck {
+        pub fn to_json(&self) -> Vec<u8> {
+            serde_json::to_vec(self).unwrap_or_default()
+        }
+
+        pub fn from_json(data: &[u8]) -> Result<Self, Error> {
+            serde_json::from_slice(data).map_err(|_| Error::PacketAckError)
+        }
+    }
+
+    /// ICS-29 relayer incentivisation for the packets a contract sends. Fees are escrowed per
+    /// packet and paid out from the ack and timeout callbacks.
+    ///
+    /// This only keeps the books, it never touches funds: the contract takes the payment for
+    /// a fee itself, checking `transferred_value` for the native token or pulling PSP22
+    /// tokens, hands what it took to `escrow`, holds it and sends the payouts returned here.
+    /// None of the demo contracts enable fees yet.
+    ///
+    /// ```ignore
+    /// // ibc_channel_open: HandshakePolicy::new("ics20-1").with_fees()
+    /// self.fees.record_channel(&msg);                  // ibc_channel_connect
+    /// let paid = vec![Coin { denom: "uatom".into(), amount: self.env().transferred_value() }];
+    /// self.fees.escrow(&channel, sequence, fee, &paid)?; // after sending a packet
+    /// let res = self.fees.wrap_ack(&msg, res);         // ibc_packet_receive
+    /// let (ack, payouts) = self.fees.on_ack(&msg)?;    // ibc_packet_ack
+    /// let payouts = self.fees.on_timeout(&msg);        // ibc_packet_timeout
+    /// ```
+    #[ink::storage_item]
+    #[derive(Default, Debug)]
+    pub struct FeeEscrow {
+        /// channels opened with the fee version wrapper
+        enabled: ink::storage::Mapping<String, bool>,
+        /// fees escrowed for a packet, by source channel and sequence
+        fees: ink::storage::Mapping<(String, u64), PacketFees>,
+        /// where a relayer wants its ack and timeout fees paid, by channel and relayer
+        payees: ink::storage::Mapping<(String, Addr), Addr>,
+        /// where a relayer wants its recv fees paid on the counterparty chain
+        counterparty_payees: ink::storage::Mapping<(String, Addr), String>,
+    }
+
+    impl FeeEscrow {
+        /// Enables fees on a channel whose negotiated version carries the fee wrapper.
+        pub fn record_channel(&mut self, msg: &IbcChannelConnectMsg) {
+            let channel = msg.channel();
+            if FeeVersion::parse(&channel.version).is_some() {
+                self.enabled.insert(&channel.endpoint.channel_id, &true);
+            }
+        }
+
+        pub fn is_enabled(&self, channel_id: &str) -> bool {
+            self.enabled.get(channel_id).unwrap_or_default()
+        }
+
+        /// Pays the ack and timeout fees `relayer` earns on the channel to `payee` instead.
+        /// The contract has to check that the relayer itself asks for this.
+        pub fn register_payee(
+            &mut self,
+            channel_id: &str,
+            relayer: Addr,
+            payee: Addr,
+        ) -> Result<(), Error> {
+            self.ensure_enabled(channel_id)?;
+            self.payees.insert((channel_id.to_string(), relayer), &payee);
+            Ok(())
+        }
+
+        /// Sets the address `relayer` puts into our acks, to be paid its recv fees on the
+        /// counterparty. The contract has to check that the relayer itself asks for this.
+        pub fn register_counterparty_payee(
+            &mut self,
+            channel_id: &str,
+            relayer: Addr,
+            counterparty_payee: String,
+        ) -> Result<(), Error> {
+            self.ensure_enabled(channel_id)?;
+            self.counterparty_payees
+                .insert((channel_id.to_string(), relayer), &counterparty_payee);
+            Ok(())
+        }
+
+        /// Where the fees `relayer` earns on the channel go, the relayer itself by default.
+        pub fn payee(&self, channel_id: &str, relayer: &Addr) -> Addr {
+            match self.payees.get((channel_id.to_string(), relayer.clone())) {
+                Some(payee) => payee,
+                None => relayer.clone(),
+            }
+        }
+
+        pub fn counterparty_payee(&self, channel_id: &str, relayer: &Addr) -> Option<String> {
+            self.counterparty_payees
+                .get((channel_id.to_string(), relayer.clone()))
+        }
+
+        /// Records a fee for a packet sent on the channel, `paid` is what the contract took for
+        /// it and has to cover [`Fee::total`]. Fees add up, anyone may incentivise a packet
+        /// further until it is acknowledged or times out.
+        pub fn escrow(
+            &mut self,
+            channel_id: &str,
+            sequence: u64,
+            fee: PacketFee,
+            paid: &[Coin],
+        ) -> Result<(), Error> {
+            self.ensure_enabled(channel_id)?;
+            for needed in fee.fee.total() {
+                let covered = paid
+                    .iter()
+                    .filter(|coin| coin.denom == needed.denom)
+                    .map(|coin| coin.amount)
+                    .sum::<u128>();
+                if covered < needed.amount {
+                    return Err(Error::InsufficientFee {
+                        denom: needed.denom,
+                    });
+                }
+            }
+            let key = (channel_id.to_string(), sequence);
+            let mut fees = self.fees.get(&key).unwrap_or_default();
+            fees.push(fee);
+            self.fees.insert(&key, &fees);
+            Ok(())
+        }
+
+        /// The fees escrowed for a packet.
+        pub fn packet_fees(&self, channel_id: &str, sequence: u64) -> Vec<PacketFee> {
+            self.fees
+                .get((channel_id.to_string(), sequence))
+                .unwrap_or_default()
+        }
+
+        /// Wraps the ack of a packet received on a fee enabled channel, so the counterparty
+        /// can pay the relayer. The relayer's address is forwarded unless it registered a
+        /// counterparty payee; without one the recv fee is refunded over there.
+        pub fn wrap_ack_for(&self, channel_id: &str, relayer: &Addr, ack: Binary) -> Binary {
+            if !self.is_enabled(channel_id) {
+                return ack;
+            }
+            let underlying_app_success = match StdAck::from_json(&ack) {
+                Ok(std_ack) => std_ack.is_success(),
+                Err(_) => true,
+            };
+            IncentivizedAck {
+                app_acknowledgement: ack,
+                forward_relayer_address: self
+                    .counterparty_payee(channel_id, relayer)
+                    .unwrap_or_default(),
+                underlying_app_success,
+            }
+            .to_json()
+            .into()
+        }
+
+        /// Wraps the ack of an `ibc_packet_receive` response. Acks written later have to be
+        /// wrapped with [`FeeEscrow::wrap_ack_for`].
+        #[cfg(feature = "ibc3")]
+        pub fn wrap_ack<T>(
+            &self,
+            msg: &IbcPacketReceiveMsg,
+            mut res: IbcReceiveResponse<T>,
+        ) -> IbcReceiveResponse<T> {
+            if !res.is_async() {
+                let ack = core::mem::take(&mut res.acknowledgement);
+                res.acknowledgement =
+                    self.wrap_ack_for(&msg.packet.dest.channel_id, &msg.relayer, ack);
+            }
+            res
+        }
+
+        /// Settles the fees of an acknowledged packet: the recv fee goes to the relayer the
+        /// ack names, the ack fee to the relayer delivering it and the timeout fee back.
+        /// Returns the application's ack and the payouts.
+        #[cfg(feature = "ibc3")]
+        pub fn on_ack(
+            &mut self,
+            msg: &IbcPacketAckMsg,
+        ) -> Result<(IbcAcknowledgement, Vec<BankMsg>), Error> {
+            let packet = &msg.original_packet;
+            if !self.is_enabled(&packet.src.channel_id) {
+                return Ok((msg.acknowledgement.clone(), vec![]));
+            }
+            let ack = IncentivizedAck::from_json(&msg.acknowledgement.data)?;
+            let ack_payee = self.payee(&packet.src.channel_id, &msg.relayer);
+            let mut payouts = Vec::new();
+            for fee in self.take_fees(&packet.src.channel_id, packet.sequence) {
+                let refund = fee.refund_address.into_string();
+                // no forward address, nobody to pay the recv fee to
+                let recv_payee = match ack.forward_relayer_address.is_empty() {
+                    true => refund.clone(),
+                    false => ack.forward_relayer_address.clone(),
+                };
+                add_payout(&mut payouts, recv_payee, &fee.fee.recv_fee);
+                add_payout(&mut payouts, ack_payee.as_str().into(), &fee.fee.ack_fee);
+                add_payout(&mut payouts, refund, &fee.fee.timeout_fee);
+            }
+            let app_ack = IbcAcknowledgement::new(ack.app_acknowledgement);
+            Ok((app_ack, into_bank_msgs(payouts)))
+        }
+
+        /// Settles the fees of a timed out packet: the timeout fee goes to the relayer that
+        /// proved the timeout, the recv and ack fees back.
+        #[cfg(feature = "ibc3")]
+        pub fn on_timeout(&mut self, msg: &IbcPacketTimeoutMsg) -> Vec<BankMsg> {
+            let packet = &msg.packet;
+            let timeout_payee = self.payee(&packet.src.channel_id, &msg.relayer);
+            let mut payouts = Vec::new();
+            for fee in self.take_fees(&packet.src.channel_id, packet.sequence) {
+                let refund = fee.refund_address.into_string();
+                add_payout(&mut payouts, timeout_payee.as_str().into(), &fee.fee.timeout_fee);
+                add_payout(&mut payouts, refund.clone(), &fee.fee.recv_fee);
+                add_payout(&mut payouts, refund, &fee.fee.ack_fee);
+            }
+            into_bank_msgs(payouts)
+        }
+
+        #[cfg(feature = "ibc3")]
+        fn take_fees(&mut self, channel_id: &str, sequence: u64) -> Vec<PacketFee> {
+            self.fees
+                .take((channel_id.to_string(), sequence))
+                .unwrap_or_default()
+        }
+
+        fn ensure_enabled(&self, channel_id: &str) -> Result<(), Error> {
+            if self.is_enabled(channel_id) {
+                Ok(())
+            } else {
+                Err(Error::FeeNotEnabled {
+                    channel: channel_id.into(),
+                })
+            }
+        }
+    }
+
+    /// Adds `coins` to `total`, merging denoms.
+    fn add_coins(total: &mut Vec<Coin>, coins: &[Coin]) {
+        for coin in coins {
+            match total.iter_mut().find(|c| c.denom == coin.denom) {
+                Some(c) => c.amount += coin.amount,
+                None => total.push(coin.clone()),
+            }
+        }
+    }
+
+    #[cfg(feature = "ibc3")]
+    fn add_payout(payouts: &mut Vec<(String, Vec<Coin>)>, to: String, coins: &[Coin]) {
+        match payouts.iter_mut().find(|(address, _)| *address == to) {
+            Some((_, total)) => add_coins(total, coins),
+            None => {
+                let mut total = Vec::new();
+                add_coins(&mut total, coins);
+                payouts.push((to, total));
+            }
+        }
+    }
+
+    #[cfg(feature = "ibc3")]
+    fn into_bank_msgs(payouts: Vec<(String, Vec<Coin>)>) -> Vec<BankMsg> {
+        payouts
+            .into_iter()
+            .map(|(to_address, amount)| {
+                let amount: Vec<Coin> = amount.into_iter().filter(|c| c.amount > 0).collect();
+                (to_address, amount)
+            })
+            .filter(|(_, amount)| !amount.is_empty())
+            .map(|(to_address, amount)| BankMsg::Send { to_address, amount })
+            .collect()
+    }
+
     #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub enum Error {
         // let thiserror implement From<StdError> for you
         StdError,
+        /// #[error("Unsupported IBC channel version: {version}")]
         InvalidIbcVersion {
             version: String,
         },
+        /// #[error("Only supports ordered channel")]
         OnlyOrderedChannel,
+        /// #[error("Only supports unordered channel")]
+        OrderedChannel,
+        /// #[error("invalid IBC channel version - got ({actual}), expected ({expected})")]
+        InvalidVersion {
+            actual: String,
+            expected: String,
+        },
+        /// #[error("Channel may not be opened with counterparty port {port}")]
+        InvalidCounterpartyPort {
+            port: String,
+        },
+        /// #[error("Failed to parse data")]
         ParseError,
+        /// #[error("Failed to serialize data")]
         SerializeError,
+        /// #[error("Invalid packet acknowledgement")]
         PacketAckError,
+        /// #[error("Packet timed out")]
         TimeoutError,
+        /// #[error("Failed to undo channel balance reduction")]
         UndoReduceChannelBalanceError,
 
         /// #[error("Got a submessage reply with unknown id: {id}")]
         UnknownReplyId {
             id: u64,
         },
+        /// #[error("Channel closing is not allowed")]
+        CannotCloseChannel,
+        /// #[error("Failed to send packet: {reason} (status {code})")]
+        SendPacketError {
+            code: u32,
+            reason: String,
+        },
+        /// #[error("Relayer fees are not enabled on channel {channel}")]
+        FeeNotEnabled {
+            channel: String,
+        },
+        /// #[error("Packet {actual} is out of order on channel {channel}, expected {expected}")]
+        PacketOutOfOrder {
+            channel: String,
+            expected: u64,
+            actual: u64,
+        },
+        /// #[error("Channel {channel} is closed")]
+        ChannelClosed {
+            channel: String,
+        },
+        /// #[error("The payment doesn't cover the {denom} the fee needs")]
+        InsufficientFee {
+            denom: String,
+        },
+    }
+
+    impl core::fmt::Display for Error {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            match self {
+                Error::StdError => write!(f, "Std error"),
+                Error::InvalidIbcVersion { version } => {
+                    write!(f, "Unsupported IBC channel version: {}", version)
+                }
+                Error::OnlyOrderedChannel => write!(f, "Only supports ordered channel"),
+                Error::OrderedChannel => write!(f, "Only supports unordered channel"),
+                Error::InvalidVersion { actual, expected } => write!(
+                    f,
+                    "invalid IBC channel version - got ({}), expected ({})",
+                    actual, expected
+                ),
+                Error::InvalidCounterpartyPort { port } => {
+                    write!(f, "Channel may not be opened with counterparty port {}", port)
+                }
+                Error::ParseError => write!(f, "Failed to parse data"),
+                Error::SerializeError => write!(f, "Failed to serialize data"),
+                Error::PacketAckError => write!(f, "Invalid packet acknowledgement"),
+                Error::TimeoutError => write!(f, "Packet timed out"),
+                Error::UndoReduceChannelBalanceError => {
+                    write!(f, "Failed to undo channel balance reduction")
+                }
+                Error::UnknownReplyId { id } => {
+                    write!(f, "Got a submessage reply with unknown id: {}", id)
+                }
+                Error::CannotCloseChannel => write!(f, "Channel closing is not allowed"),
+                Error::SendPacketError { code, reason } => {
+                    write!(f, "Failed to send packet: {} (status {})", reason, code)
+                }
+                Error::FeeNotEnabled { channel } => {
+                    write!(f, "Relayer fees are not enabled on channel {}", channel)
+                }
+                Error::PacketOutOfOrder {
+                    channel,
+                    expected,
+                    actual,
+                } => write!(
+                    f,
+                    "Packet {} is out of order on channel {}, expected {}",
+                    actual, channel, expected
+                ),
+                Error::ChannelClosed { channel } => write!(f, "Channel {} is closed", channel),
+                Error::InsufficientFee { denom } => {
+                    write!(f, "The payment doesn't cover the {} the fee needs", denom)
+                }
+            }
+        }
+    }
+
+    impl AckError for Error {
+        fn code(&self) -> u32 {
+            match self {
+                Error::StdError => 1,
+                Error::InvalidIbcVersion { .. } => 2,
+                Error::OnlyOrderedChannel => 3,
+                Error::OrderedChannel => 4,
+                Error::InvalidVersion { .. } => 5,
+                Error::InvalidCounterpartyPort { .. } => 6,
+                Error::ParseError => 7,
+                Error::SerializeError => 8,
+                Error::PacketAckError => 9,
+                Error::TimeoutError => 10,
+                Error::UndoReduceChannelBalanceError => 11,
+                Error::UnknownReplyId { .. } => 12,
+                Error::CannotCloseChannel => 13,
+                Error::SendPacketError { .. } => 14,
+                Error::FeeNotEnabled { .. } => 15,
+                Error::PacketOutOfOrder { .. } => 16,
+                Error::ChannelClosed { .. } => 17,
+                Error::InsufficientFee { .. } => 18,
+            }
+        }
     }
 
     /// Defines the storage of your contract.
@@ -1140,5 +2832,632 @@ pub mod ibc {
     mod tests {
         /// Imports all the definitions from the outer scope so we can use them here.
         use super::*;
+
+        #[test]
+        fn ics20_packet_matches_ibc_go_bytes() {
+            let packet = Ics20Packet::new(
+                999000,
+                "ERT",
+                "cosmos1xh2jvz9ecty8qdctlgscmys2dr5gz729k0l7x4",
+                "0x8eaf04151687736326c9fea17e25fc5287613693c912909cb226aa4794f26a48",
+                None,
+            );
+            assert_eq!(
+                String::from_utf8(packet.to_json()).unwrap(),
+                r#"{"amount":"999000","denom":"ERT","receiver":"0x8eaf04151687736326c9fea17e25fc5287613693c912909cb226aa4794f26a48","sender":"cosmos1xh2jvz9ecty8qdctlgscmys2dr5gz729k0l7x4"}"#
+            );
+            assert_eq!(Ics20Packet::from_json(&packet.to_json()), Ok(packet));
+        }
+
+        #[test]
+        fn ics20_packet_escapes_memo_like_go() {
+            let memo = "<\"x\"&\n\u{1}>";
+            let packet = Ics20Packet::new(1, "transfer/channel-0/uatom", "a", "b", Some(memo.into()));
+            assert_eq!(
+                String::from_utf8(packet.to_json()).unwrap(),
+                r#"{"amount":"1","denom":"transfer/channel-0/uatom","memo":"\u003c\"x\"\u0026\n\u0001\u003e","receiver":"b","sender":"a"}"#
+            );
+            assert_eq!(Ics20Packet::from_json(&packet.to_json()), Ok(packet));
+        }
+
+        #[test]
+        fn ink_hook_is_read_from_memo() {
+            let memo = r#"{"ink":{"contract":"0x01","selector":"0xdeadbeef","args":"AQID"}}"#;
+            let hook = InkHook::from_memo(memo).unwrap().unwrap();
+            assert_eq!(hook.contract, "0x01");
+            assert_eq!(hook.call_data(), vec![0xde, 0xad, 0xbe, 0xef, 1, 2, 3]);
+            assert_eq!(
+                serde_json::to_string(&hook).unwrap(),
+                r#"{"contract":"0x01","selector":"0xdeadbeef","args":"AQID"}"#
+            );
+
+            // other middleware and free text memos are not hooks
+            assert_eq!(InkHook::from_memo(r#"{"forward":{}}"#), Ok(None));
+            assert_eq!(InkHook::from_memo("thanks for the coffee"), Ok(None));
+            assert_eq!(
+                InkHook::from_memo(r#"{"ink":{"contract":"0x01","selector":"0xdead"}}"#),
+                Err(Error::ParseError)
+            );
+        }
+
+        #[test]
+        fn forward_metadata_is_read_from_memo() {
+            let memo = r#"{"forward":{"receiver":"osmo1xyz","port":"transfer","channel":"channel-1","retries":2,"next":{"forward":{"receiver":"juno1abc","port":"transfer","channel":"channel-7"}}}}"#;
+            let forward = ForwardMetadata::from_memo(memo).unwrap().unwrap();
+            assert_eq!(forward.channel, "channel-1");
+            assert_eq!(forward.timeout, None);
+            assert_eq!(forward.retries, Some(2));
+            let next = ForwardMetadata::from_memo(forward.next.as_deref().unwrap())
+                .unwrap()
+                .unwrap();
+            assert_eq!((next.receiver.as_str(), next.next), ("juno1abc", None));
+
+            assert_eq!(ForwardMetadata::from_memo("gm"), Ok(None));
+            assert_eq!(
+                ForwardMetadata::from_memo(r#"{"forward":{"receiver":"osmo1xyz"}}"#),
+                Err(Error::ParseError)
+            );
+        }
+
+        #[test]
+        fn ics20_packet_rejects_numeric_amount() {
+            let data = br#"{"amount":5,"denom":"ERT","receiver":"b","sender":"a"}"#;
+            assert_eq!(Ics20Packet::from_json(data), Err(Error::ParseError));
+            let data = br#"{"amount":"5","denom":"ERT","memo":"","receiver":"b","sender":"a"}"#;
+            assert_eq!(Ics20Packet::from_json(data).unwrap().memo, None);
+        }
+
+        #[test]
+        fn std_ack_matches_ibc_go_envelope() {
+            let ok = StdAck::success([1u8]);
+            assert_eq!(ok.to_json(), br#"{"result":"AQ=="}"#.to_vec());
+            assert_eq!(IbcAcknowledgement::new(ok.clone()).std_ack(), Ok(ok));
+
+            let err = StdAck::from_error(&Error::TimeoutError);
+            assert_eq!(
+                err.to_json(),
+                br#"{"error":"ABCI code: 10: Packet timed out"}"#.to_vec()
+            );
+            assert!(!StdAck::from_json(&err.to_json()).unwrap().is_success());
+
+            assert_eq!(
+                IbcAcknowledgement::new(b"garbage".to_vec()).std_ack(),
+                Err(Error::ParseError)
+            );
+        }
+
+        #[test]
+        fn addr_round_trips_account_ids() {
+            let account = AccountId::from([0xab; 32]);
+            let addr = Addr::from(account);
+            assert_eq!(addr.as_str(), "0x".to_owned() + &"ab".repeat(32));
+            assert_eq!(addr.to_account_id(), Some(account));
+            assert_eq!(Addr::unchecked("0xab").to_account_id(), None);
+            assert_eq!(Addr::unchecked("cosmos1xyz").to_account_id(), None);
+        }
+
+        #[test]
+        fn binary_serializes_as_base64() {
+            let data = Binary::from([1u8, 2, 3]);
+            assert_eq!(serde_json::to_string(&data).unwrap(), r#""AQID""#);
+            assert_eq!(serde_json::from_str::<Binary>(r#""AQID""#).unwrap(), data);
+            assert_eq!(Binary::from_base64("AQID"), Ok(data.clone()));
+            assert_eq!(Binary::from_base64("AQI"), Err(Error::ParseError));
+            // SCALE keeps the plain byte vector layout
+            assert_eq!(data.encode(), vec![1u8, 2, 3].encode());
+
+            let msg = WasmMsg::Execute {
+                contract_addr: "contract".into(),
+                msg: br#"{"ping":{}}"#.to_vec().into(),
+                funds: vec![],
+            };
+            assert_eq!(
+                serde_json::to_string(&msg).unwrap(),
+                r#"{"execute":{"contract_addr":"contract","msg":"eyJwaW5nIjp7fX0=","funds":[]}}"#
+            );
+            let msg = CosmosMsg::<Empty>::Bank(BankMsg::Burn { amount: vec![] });
+            assert_eq!(serde_json::to_string(&msg).unwrap(), r#"{"bank":{"burn":{"amount":[]}}}"#);
+        }
+
+        #[test]
+        fn error_codes_are_stable() {
+            let err = Error::InvalidVersion {
+                actual: "ics20-2".into(),
+                expected: "ics20-1".into(),
+            };
+            assert_eq!(err.code(), 5);
+            assert_eq!(
+                StdAck::from_error(&err),
+                StdAck::error("ABCI code: 5: invalid IBC channel version - got (ics20-2), expected (ics20-1)")
+            );
+            assert_eq!(Error::UnknownReplyId { id: 3 }.code(), 12);
+            let err = Error::SendPacketError {
+                code: 11,
+                reason: "Channel is not open".into(),
+            };
+            assert_eq!(err.code(), 14);
+            assert_eq!(err.to_string(), "Failed to send packet: Channel is not open (status 11)");
+            let err = Error::FeeNotEnabled {
+                channel: "channel-0".into(),
+            };
+            assert_eq!(err.code(), 15);
+        }
+
+        #[test]
+        fn ibc_timeout_follows_ics04_rules() {
+            let ns = IbcTimestamp::from_nanos;
+            let at_block = IbcTimeout::with_block(IbcTimeoutBlock::new(1, 100));
+            assert!(!at_block.is_expired(&IbcTimeoutBlock::new(1, 99), ns(u64::MAX)));
+            assert!(at_block.is_expired(&IbcTimeoutBlock::new(1, 100), ns(0)));
+            // a higher revision is always later, whatever the height
+            assert!(at_block.is_expired(&IbcTimeoutBlock::new(2, 1), ns(0)));
+
+            let at_time = IbcTimeout::with_timestamp(ns(5_000));
+            assert!(!at_time.is_expired(&IbcTimeoutBlock::new(9, 9), ns(4_999)));
+            assert!(at_time.is_expired(&IbcTimeoutBlock::new(0, 0), ns(5_000)));
+
+            let both = IbcTimeout::with_both(IbcTimeoutBlock::new(0, 10), ns(5_000));
+            assert_eq!(both.block(), Some(IbcTimeoutBlock::new(0, 10)));
+            assert_eq!(both.timestamp(), Some(ns(5_000)));
+            assert!(both.is_expired(&IbcTimeoutBlock::new(0, 10), ns(0)));
+            assert!(both.is_expired(&IbcTimeoutBlock::new(0, 1), ns(5_000)));
+
+            // zero values disable the respective check
+            let disabled = IbcTimeout::with_both(IbcTimeoutBlock::new(0, 0), ns(0));
+            assert!(!disabled.is_expired(&IbcTimeoutBlock::new(5, 5), ns(u64::MAX)));
+        }
+
+        #[test]
+        fn ibc_timestamp_converts_units() {
+            let block_time_ms = 1_700_000_000_123;
+            let now = IbcTimestamp::from_millis(block_time_ms).unwrap();
+            assert_eq!(now.nanos(), 1_700_000_000_123_000_000);
+            assert_eq!(now.millis(), block_time_ms);
+            assert_eq!(now.seconds(), 1_700_000_000);
+
+            let timeout = now.plus_seconds(600).unwrap();
+            assert_eq!(timeout.millis(), block_time_ms + 600_000);
+            assert_eq!(timeout.minus_seconds(600), Some(now));
+            assert_eq!(now.plus_millis(1).unwrap().nanos(), now.nanos() + 1_000_000);
+            assert_eq!(IbcTimestamp::from_seconds(2), IbcTimestamp::from_millis(2_000));
+
+            assert_eq!(IbcTimestamp::from_millis(u64::MAX), None);
+            assert_eq!(IbcTimestamp::from_seconds(u64::MAX / 1_000), None);
+            assert_eq!(now.plus_seconds(u64::MAX / 1_000_000_000), None);
+            assert_eq!(now.minus_nanos(u64::MAX), None);
+
+            assert_eq!(serde_json::to_string(&now).unwrap(), r#""1700000000123000000""#);
+            assert_eq!(
+                serde_json::from_str::<IbcTimestamp>(r#""1700000000123000000""#).unwrap(),
+                now
+            );
+        }
+
+        fn channel(order: IbcOrder, version: &str, counterparty_port: &str) -> IbcChannel {
+            IbcChannel {
+                endpoint: IbcEndpoint {
+                    port_id: "ics20".into(),
+                    channel_id: "channel-1".into(),
+                },
+                counterparty_endpoint: IbcEndpoint {
+                    port_id: counterparty_port.into(),
+                    channel_id: "channel-7".into(),
+                },
+                order,
+                version: version.into(),
+                connection_id: "connection-0".into(),
+            }
+        }
+
+        #[test]
+        fn handshake_policy_negotiates_version() {
+            let policy = HandshakePolicy::new("ics20-1").with_order(IbcOrder::Unordered);
+
+            let init = IbcChannelOpenMsg::OpenInit {
+                channel: channel(IbcOrder::Unordered, "", "transfer"),
+            };
+            assert_eq!(policy.validate_open(&init), Ok("ics20-1".into()));
+
+            let try_msg = IbcChannelOpenMsg::OpenTry {
+                channel: channel(IbcOrder::Unordered, "", "transfer"),
+                counterparty_version: "ics20-2".into(),
+            };
+            assert_eq!(
+                policy.validate_open(&try_msg),
+                Err(Error::InvalidVersion {
+                    actual: "ics20-2".into(),
+                    expected: "ics20-1".into()
+                })
+            );
+            let policy = policy.allow_version("ics20-2");
+            assert_eq!(policy.validate_open(&try_msg), Ok("ics20-2".into()));
+
+            let ack = IbcChannelConnectMsg::OpenAck {
+                channel: channel(IbcOrder::Unordered, "ics20-2", "transfer"),
+                counterparty_version: "ics20-1".into(),
+            };
+            assert!(matches!(
+                policy.validate_connect(&ack),
+                Err(Error::InvalidVersion { .. })
+            ));
+            let confirm = IbcChannelConnectMsg::OpenConfirm {
+                channel: channel(IbcOrder::Unordered, "ics20-2", "transfer"),
+            };
+            assert_eq!(policy.validate_connect(&confirm), Ok("ics20-2".into()));
+        }
+
+        #[test]
+        fn handshake_policy_checks_order_and_port() {
+            let policy = HandshakePolicy::new("ics27-1")
+                .with_order(IbcOrder::Ordered)
+                .with_counterparty_port(PortRule::Prefix("icacontroller-".into()));
+
+            let unordered = IbcChannelOpenMsg::OpenInit {
+                channel: channel(IbcOrder::Unordered, "ics27-1", "icacontroller-alice"),
+            };
+            assert_eq!(policy.validate_open(&unordered), Err(Error::OnlyOrderedChannel));
+
+            let wrong_port = IbcChannelOpenMsg::OpenInit {
+                channel: channel(IbcOrder::Ordered, "ics27-1", "transfer"),
+            };
+            assert_eq!(
+                policy.validate_open(&wrong_port),
+                Err(Error::InvalidCounterpartyPort {
+                    port: "transfer".into()
+                })
+            );
+
+            let unknown_version = IbcChannelOpenMsg::OpenInit {
+                channel: channel(IbcOrder::Ordered, "ics20-1", "icacontroller-alice"),
+            };
+            assert_eq!(
+                policy.validate_open(&unknown_version),
+                Err(Error::InvalidIbcVersion {
+                    version: "ics20-1".into()
+                })
+            );
+
+            let ordered = HandshakePolicy::new("ics721-1").with_order(IbcOrder::Unordered);
+            let msg = IbcChannelOpenMsg::OpenInit {
+                channel: channel(IbcOrder::Ordered, "ics721-1", "nft"),
+            };
+            assert_eq!(ordered.validate_open(&msg), Err(Error::OrderedChannel));
+        }
+
+        #[ink::test]
+        fn sequence_tracker_enforces_order() {
+            let mut tracker = SequenceTracker::default();
+            let ordered = channel(IbcOrder::Ordered, "ics27-1", "icacontroller-alice");
+            tracker.open(&IbcChannelConnectMsg::OpenConfirm { channel: ordered });
+            let at = |sequence, src: &str, dest: &str| {
+                let mut packet = packet();
+                packet.sequence = sequence;
+                packet.src.channel_id = src.into();
+                packet.dest.channel_id = dest.into();
+                packet
+            };
+
+            tracker.on_receive(&at(1, "channel-7", "channel-1")).unwrap();
+            assert_eq!(
+                tracker.on_receive(&at(1, "channel-7", "channel-1")),
+                Err(Error::PacketOutOfOrder {
+                    channel: "channel-1".into(),
+                    expected: 2,
+                    actual: 1
+                })
+            );
+            assert!(tracker.on_receive(&at(3, "channel-7", "channel-1")).is_err());
+            tracker.on_receive(&at(2, "channel-7", "channel-1")).unwrap();
+
+            tracker.on_send("channel-1", 1);
+            tracker.on_send("channel-1", 2);
+            assert!(tracker.on_ack(&at(2, "channel-1", "channel-7")).is_err());
+            tracker.on_ack(&at(1, "channel-1", "channel-7")).unwrap();
+            tracker.on_timeout(&at(2, "channel-1", "channel-7")).unwrap();
+            let mut expected = ChannelSequences::new(IbcOrder::Ordered);
+            expected.next_send = 3;
+            expected.next_recv = 3;
+            expected.next_ack = 3;
+            expected.closed = true;
+            assert_eq!(tracker.get("channel-1"), Some(expected));
+            // the timeout closed the ordered channel
+            assert_eq!(
+                tracker.on_receive(&at(3, "channel-7", "channel-1")),
+                Err(Error::ChannelClosed {
+                    channel: "channel-1".into()
+                })
+            );
+            assert!(tracker.on_ack(&at(3, "channel-1", "channel-7")).is_err());
+
+            // unordered channels follow whatever arrives
+            tracker.on_receive(&at(5, "channel-7", "channel-2")).unwrap();
+            tracker.on_receive(&at(4, "channel-7", "channel-2")).unwrap();
+            assert_eq!(tracker.get("channel-2").unwrap().next_recv, 6);
+            tracker.on_timeout(&at(1, "channel-2", "channel-7")).unwrap();
+            tracker.on_receive(&at(6, "channel-7", "channel-2")).unwrap();
+        }
+
+        #[test]
+        fn handshake_policy_negotiates_fee_version() {
+            let wrapped = FeeVersion::new("ics20-1").to_version();
+            assert_eq!(wrapped, r#"{"fee_version":"ics29-1","app_version":"ics20-1"}"#);
+            let try_msg = IbcChannelOpenMsg::OpenTry {
+                channel: channel(IbcOrder::Unordered, "", "transfer"),
+                counterparty_version: wrapped.clone(),
+            };
+            let policy = HandshakePolicy::new("ics20-1");
+            assert!(matches!(
+                policy.validate_open(&try_msg),
+                Err(Error::InvalidVersion { .. })
+            ));
+
+            let policy = policy.with_fees();
+            assert_eq!(policy.validate_open(&try_msg), Ok(wrapped.clone()));
+            let init = IbcChannelOpenMsg::OpenInit {
+                channel: channel(IbcOrder::Unordered, "", "transfer"),
+            };
+            assert_eq!(policy.validate_open(&init), Ok("ics20-1".into()));
+            let confirm = IbcChannelConnectMsg::OpenConfirm {
+                channel: channel(IbcOrder::Unordered, &wrapped, "transfer"),
+            };
+            assert_eq!(policy.validate_connect(&confirm), Ok(wrapped));
+
+            let other_app = FeeVersion::new("ics20-2").to_version();
+            let init = IbcChannelOpenMsg::OpenInit {
+                channel: channel(IbcOrder::Unordered, &other_app, "transfer"),
+            };
+            assert_eq!(
+                policy.validate_open(&init),
+                Err(Error::InvalidIbcVersion { version: other_app })
+            );
+        }
+
+        #[test]
+        fn fee_total_covers_either_payout() {
+            let coin = |denom: &str, amount| Coin {
+                denom: denom.into(),
+                amount,
+            };
+            let fee = Fee {
+                recv_fee: vec![coin("uatom", 10)],
+                ack_fee: vec![coin("uatom", 5), coin("ERT", 1)],
+                timeout_fee: vec![coin("uatom", 20), coin("uosmo", 3)],
+            };
+            assert_eq!(
+                fee.total(),
+                vec![coin("uatom", 20), coin("ERT", 1), coin("uosmo", 3)]
+            );
+        }
+
+        #[cfg(feature = "ibc3")]
+        #[ink::test]
+        fn fee_escrow_pays_relayers() {
+            let coin = |amount| Coin {
+                denom: "uatom".into(),
+                amount,
+            };
+            let fee = PacketFee {
+                fee: Fee {
+                    recv_fee: vec![coin(10)],
+                    ack_fee: vec![coin(5)],
+                    timeout_fee: vec![coin(7)],
+                },
+                refund_address: Addr::unchecked("0xa11ce"),
+            };
+            let relayer = Addr::unchecked("0xb0b");
+            let paid = fee.fee.total();
+            let mut escrow = FeeEscrow::default();
+            assert_eq!(
+                escrow.escrow("channel-7", 3, fee.clone(), &paid),
+                Err(Error::FeeNotEnabled {
+                    channel: "channel-7".into()
+                })
+            );
+
+            let mut sending = channel(IbcOrder::Unordered, "", "transfer");
+            sending.endpoint.channel_id = "channel-7".into();
+            sending.version = FeeVersion::new("ics20-1").to_version();
+            escrow.record_channel(&IbcChannelConnectMsg::OpenConfirm { channel: sending });
+            assert!(escrow.is_enabled("channel-7"));
+            // the fee has to be paid in full
+            assert_eq!(
+                escrow.escrow("channel-7", 3, fee.clone(), &[coin(14)]),
+                Err(Error::InsufficientFee {
+                    denom: "uatom".into()
+                })
+            );
+            assert_eq!(
+                escrow.escrow("channel-7", 3, fee.clone(), &[]),
+                Err(Error::InsufficientFee {
+                    denom: "uatom".into()
+                })
+            );
+            escrow.escrow("channel-7", 3, fee.clone(), &paid).unwrap();
+            escrow.escrow("channel-7", 3, fee.clone(), &paid).unwrap();
+            assert_eq!(escrow.packet_fees("channel-7", 3).len(), 2);
+            let payee = Addr::unchecked("0xca401");
+            escrow
+                .register_payee("channel-7", relayer.clone(), payee.clone())
+                .unwrap();
+
+            // the counterparty names the relayer that delivered the packet
+            let app_ack = Binary::from(StdAck::success([1u8]));
+            let ack = IncentivizedAck {
+                app_acknowledgement: app_ack.clone(),
+                forward_relayer_address: "cosmos1relayer".into(),
+                underlying_app_success: true,
+            };
+            let msg = IbcPacketAckMsg::new(
+                IbcAcknowledgement::new(ack.to_json()),
+                packet(),
+                relayer.clone(),
+            );
+            let (ack, payouts) = escrow.on_ack(&msg).unwrap();
+            assert_eq!(ack, IbcAcknowledgement::new(app_ack));
+            let send = |to: &str, amount| BankMsg::Send {
+                to_address: to.into(),
+                amount: vec![coin(amount)],
+            };
+            assert_eq!(
+                payouts,
+                vec![send("cosmos1relayer", 20), send("0xca401", 10), send("0xa11ce", 14)]
+            );
+            assert!(escrow.packet_fees("channel-7", 3).is_empty());
+
+            escrow.escrow("channel-7", 4, fee, &paid).unwrap();
+            let mut timed_out = packet();
+            timed_out.sequence = 4;
+            let payouts = escrow.on_timeout(&IbcPacketTimeoutMsg::new(timed_out, relayer));
+            assert_eq!(payouts, vec![send("0xca401", 7), send("0xa11ce", 15)]);
+        }
+
+        #[cfg(feature = "ibc3")]
+        #[ink::test]
+        fn fee_escrow_wraps_acks_on_fee_channels() {
+            let relayer = Addr::unchecked("0xb0b");
+            let mut escrow = FeeEscrow::default();
+            let res = IbcReceiveResponse::<Empty>::new().set_ack(StdAck::error("no funds"));
+            let msg = IbcPacketReceiveMsg::new(packet(), relayer.clone());
+            let res = escrow.wrap_ack(&msg, res);
+            assert_eq!(res.acknowledgement, Binary::from(StdAck::error("no funds")));
+
+            let version = FeeVersion::new("ics20-1").to_version();
+            let receiving = channel(IbcOrder::Unordered, &version, "transfer");
+            escrow.record_channel(&IbcChannelConnectMsg::OpenConfirm { channel: receiving });
+            escrow
+                .register_counterparty_payee("channel-1", relayer, "cosmos1payee".into())
+                .unwrap();
+            let res = escrow.wrap_ack(&msg, res);
+            assert_eq!(
+                IncentivizedAck::from_json(&res.acknowledgement),
+                Ok(IncentivizedAck {
+                    app_acknowledgement: StdAck::error("no funds").into(),
+                    forward_relayer_address: "cosmos1payee".into(),
+                    underlying_app_success: false,
+                })
+            );
+            let res = IbcReceiveResponse::<Empty>::without_ack();
+            assert!(escrow.wrap_ack(&msg, res).is_async());
+        }
+
+        #[test]
+        fn denom_trace_matches_ibc_go() {
+            let trace = DenomTrace::parse("transfer/channel-0/uatom");
+            assert_eq!(trace.path, "transfer/channel-0");
+            assert_eq!(trace.base_denom, "uatom");
+            assert_eq!(
+                trace.ibc_denom(),
+                "ibc/27394FB092D2ECCD56123C74F36E4C1F926001CEADA9CA97EA622B25F41E5EB2"
+            );
+            assert_eq!(parse_ibc_denom_hash(&trace.ibc_denom()), Some(trace.hash()));
+
+            let native = DenomTrace::parse("uatom");
+            assert!(native.is_native());
+            assert_eq!(native.ibc_denom(), "uatom");
+
+            // slashes after the last channel hop belong to the base denom
+            let pool = DenomTrace::parse("transfer/channel-1/transfer/channel-7/gamm/pool/1");
+            assert_eq!(pool.path, "transfer/channel-1/transfer/channel-7");
+            assert_eq!(pool.base_denom, "gamm/pool/1");
+            assert_eq!(DenomTrace::parse("transfer/channel-0").base_denom, "transfer/channel-0");
+            assert!(DenomTrace::parse("transfer/chan-0/uatom").is_native());
+        }
+
+        #[test]
+        fn denom_trace_prefixes_and_unwinds() {
+            let trace = DenomTrace::parse("uatom").add_prefix("transfer", "channel-3");
+            assert_eq!(trace.full_path(), "transfer/channel-3/uatom");
+            let twice = trace.add_prefix("ics20", "channel-1");
+            assert_eq!(twice.full_path(), "ics20/channel-1/transfer/channel-3/uatom");
+
+            assert_eq!(twice.remove_prefix("transfer", "channel-3"), None);
+            assert_eq!(twice.remove_prefix("ics20", "channel-1"), Some(trace.clone()));
+            let home = trace.remove_prefix("transfer", "channel-3").unwrap();
+            assert!(home.is_native());
+            assert_eq!(trace.remove_prefix("transfer", "channel-30"), None);
+
+            assert!(receiver_chain_is_source("transfer", "channel-3", "transfer/channel-3/uatom"));
+            assert!(sender_chain_is_source("transfer", "channel-3", "transfer/channel-30/uatom"));
+            assert!(sender_chain_is_source("transfer", "channel-3", "uatom"));
+        }
+
+        #[ink::test]
+        fn denom_traces_resolve_hashes() {
+            let mut traces = DenomTraces::default();
+            let trace = DenomTrace::parse("transfer/channel-0/uatom");
+            let denom = traces.insert(&trace);
+            assert_eq!(traces.resolve(&denom), Some(trace));
+            assert_eq!(traces.resolve("uatom").unwrap().full_path(), "uatom");
+            assert_eq!(traces.resolve("ibc/27394FB0"), None);
+            assert_eq!(
+                traces.resolve("ibc/0000000000000000000000000000000000000000000000000000000000000000"),
+                None
+            );
+        }
+
+        fn packet() -> IbcPacket {
+            IbcPacket {
+                data: b"{}".to_vec().into(),
+                src: IbcEndpoint {
+                    port_id: "transfer".into(),
+                    channel_id: "channel-7".into(),
+                },
+                dest: IbcEndpoint {
+                    port_id: "ics20".into(),
+                    channel_id: "channel-1".into(),
+                },
+                sequence: 3,
+                timeout: IbcTimeout::with_timestamp(IbcTimestamp::from_nanos(5_000)),
+            }
+        }
+
+        #[cfg(not(feature = "ibc3"))]
+        #[test]
+        fn open_response_is_unit_without_ibc3() {
+            let policy = HandshakePolicy::new("ics20-1");
+            let init = IbcChannelOpenMsg::OpenInit {
+                channel: channel(IbcOrder::Unordered, "", "transfer"),
+            };
+            assert_eq!(policy.open_response(&init), Ok(()));
+
+            let msg = IbcPacketReceiveMsg::new(packet());
+            assert_eq!(msg.packet.sequence, 3);
+            let msg = IbcPacketTimeoutMsg::new(packet());
+            assert_eq!(msg.packet.src.channel_id, "channel-7");
+        }
+
+        #[cfg(feature = "ibc3")]
+        #[test]
+        fn open_response_negotiates_version_with_ibc3() {
+            let policy = HandshakePolicy::new("ics20-1").allow_version("ics20-2");
+            let init = IbcChannelOpenMsg::OpenInit {
+                channel: channel(IbcOrder::Unordered, "", "transfer"),
+            };
+            assert_eq!(
+                policy.open_response(&init),
+                Ok(Some(Ibc3ChannelOpenResponse {
+                    version: "ics20-1".into()
+                }))
+            );
+            let try_msg = IbcChannelOpenMsg::OpenTry {
+                channel: channel(IbcOrder::Unordered, "", "transfer"),
+                counterparty_version: "ics20-2".into(),
+            };
+            assert_eq!(
+                policy.open_response(&try_msg),
+                Ok(Some(Ibc3ChannelOpenResponse {
+                    version: "ics20-2".into()
+                }))
+            );
+
+            let relayer = Addr::unchecked("0x01");
+            let msg = IbcPacketReceiveMsg::new(packet(), relayer.clone());
+            assert_eq!(msg.relayer, relayer);
+            let ack = IbcAcknowledgement::new(StdAck::success([1u8]));
+            let msg = IbcPacketAckMsg::new(ack, packet(), relayer.clone());
+            assert_eq!(msg.relayer, relayer);
+            let msg = IbcPacketTimeoutMsg::new(packet(), relayer.clone());
+            assert_eq!(msg.relayer, relayer);
+        }
     }
 }
@@ -0,0 +1,687 @@
+//! An off-chain simulation of two chains connected by an IBC relayer.
+//!
+//! Both apps live in the ink! off-chain environment, each under its own account, so their
+//! storage stays separate. The simulator runs the four step channel handshake, turns the
+//! `IbcMsg::SendPacket`/`IbcMsg::Transfer` messages the apps return into packets and
+//! relays them, delivering either `ibc_packet_receive` + `ibc_packet_ack` or
+//! `ibc_packet_timeout` depending on the simulated height and time of the counterparty.
+//!
+//! ```ignore
+//! let a = Chain::instantiate(accounts.alice, "ics721-a", || Ics721demo::default());
+//! let b = Chain::instantiate(accounts.bob, "ics721-b", || Ics721demo::default());
+//! let mut sim = IbcSimulator::new(a, b);
+//! let (channel_a, _) = sim.open_channel(IbcOrder::Unordered, "ics721-1")?;
+//! sim.a.send_packet(&channel_a, data, timeout);
+//! let outcomes = sim.relay()?;
+//! ```
+//!
+//! Messages other than the two IBC sends are ignored, and a `reply` is never invoked.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use ink::env::DefaultEnvironment;
+use ink::primitives::AccountId;
+
+use crate::ibc::*;
+
+/// The IBC entry points of an app, as seen by the simulator.
+///
+/// Every `BaseIbc` contract implements this through the blanket impl below. Test doubles
+/// that are not ink! contracts can implement it directly.
+pub trait IbcApp {
+    fn ibc_channel_open(&self, msg: IbcChannelOpenMsg) -> Result<IbcChannelOpenResponse, Error>;
+
+    fn ibc_channel_connect(&mut self, msg: IbcChannelConnectMsg)
+        -> Result<IbcBasicResponse, Error>;
+
+    fn ibc_channel_close(&self, msg: IbcChannelCloseMsg) -> Result<IbcBasicResponse, Error>;
+
+    fn ibc_packet_receive(&mut self, msg: IbcPacketReceiveMsg)
+        -> Result<IbcReceiveResponse, Error>;
+
+    fn ibc_packet_ack(&mut self, msg: IbcPacketAckMsg) -> Result<IbcBasicResponse, Error>;
+
+    fn ibc_packet_timeout(&mut self, msg: IbcPacketTimeoutMsg) -> Result<IbcBasicResponse, Error>;
+}
+
+// ink! generates an associated output type per trait message; contracts set them to the
+// declared return types.
+impl<T> IbcApp for T
+where
+    T: BaseIbc<
+        ibcChannelOpenOutput = Result<IbcChannelOpenResponse, Error>,
+        ibcChannelConnectOutput = Result<IbcBasicResponse, Error>,
+        ibcChannelCloseOutput = Result<IbcBasicResponse, Error>,
+        ibcPacketReceiveOutput = Result<IbcReceiveResponse, Error>,
+        ibcPacketAckOutput = Result<IbcBasicResponse, Error>,
+        ibcPacketTimeoutOutput = Result<IbcBasicResponse, Error>,
+    >,
+{
+    fn ibc_channel_open(&self, msg: IbcChannelOpenMsg) -> Result<IbcChannelOpenResponse, Error> {
+        BaseIbc::ibc_channel_open(self, msg)
+    }
+
+    fn ibc_channel_connect(
+        &mut self,
+        msg: IbcChannelConnectMsg,
+    ) -> Result<IbcBasicResponse, Error> {
+        BaseIbc::ibc_channel_connect(self, msg)
+    }
+
+    fn ibc_channel_close(&self, msg: IbcChannelCloseMsg) -> Result<IbcBasicResponse, Error> {
+        BaseIbc::ibc_channel_close(self, msg)
+    }
+
+    fn ibc_packet_receive(
+        &mut self,
+        msg: IbcPacketReceiveMsg,
+    ) -> Result<IbcReceiveResponse, Error> {
+        BaseIbc::ibc_packet_receive(self, msg)
+    }
+
+    fn ibc_packet_ack(&mut self, msg: IbcPacketAckMsg) -> Result<IbcBasicResponse, Error> {
+        BaseIbc::ibc_packet_ack(self, msg)
+    }
+
+    fn ibc_packet_timeout(&mut self, msg: IbcPacketTimeoutMsg) -> Result<IbcBasicResponse, Error> {
+        BaseIbc::ibc_packet_timeout(self, msg)
+    }
+}
+
+/// The `Addr` a contract account is known by: `0x` followed by the hex encoded `AccountId`.
+pub fn account_addr(account: &AccountId) -> Addr {
+    Addr::from(*account)
+}
+
+/// Block time of the simulated chains at genesis, in ink! milliseconds.
+pub const GENESIS_TIME_MS: u64 = 1_700_000_000_000;
+
+/// One side of the simulation: an app deployed on a chain with its own clock.
+pub struct Chain<A> {
+    pub app: A,
+    pub account: AccountId,
+    pub port_id: String,
+    pub height: IbcTimeoutBlock,
+    pub time: IbcTimestamp,
+    channels: BTreeMap<String, IbcChannel>,
+    next_sequence: BTreeMap<String, u64>,
+    outbox: VecDeque<IbcPacket>,
+    /// received packets the app acknowledges later, by our channel and sequence
+    awaiting_ack: BTreeMap<(String, u64), IbcPacket>,
+    /// acks written with `IbcMsg::WriteAcknowledgement` but not relayed yet
+    ack_outbox: VecDeque<(IbcPacket, Binary)>,
+}
+
+impl<A> Chain<A> {
+    /// Deploys the app returned by `instantiate` at `account`, bound to `port_id`.
+    pub fn instantiate(account: AccountId, port_id: &str, instantiate: impl FnOnce() -> A) -> Self {
+        let time = IbcTimestamp::from_millis(GENESIS_TIME_MS).expect("genesis time fits");
+        enter(account, time);
+        Chain {
+            app: instantiate(),
+            account,
+            port_id: port_id.into(),
+            height: IbcTimeoutBlock::new(1, 1),
+            time,
+            channels: BTreeMap::new(),
+            next_sequence: BTreeMap::new(),
+            outbox: VecDeque::new(),
+            awaiting_ack: BTreeMap::new(),
+            ack_outbox: VecDeque::new(),
+        }
+    }
+
+    pub fn addr(&self) -> Addr {
+        account_addr(&self.account)
+    }
+
+    /// Runs `f` against the app with the ink! environment pointed at this chain.
+    pub fn call<R>(&mut self, f: impl FnOnce(&mut A) -> R) -> R {
+        enter(self.account, self.time);
+        f(&mut self.app)
+    }
+
+    /// Calls an execute style message and queues the packets its response sends.
+    pub fn execute<T, E>(
+        &mut self,
+        f: impl FnOnce(&mut A) -> Result<Response<T>, E>,
+    ) -> Result<Response<T>, E> {
+        let response = self.call(f)?;
+        self.dispatch(&response.messages);
+        Ok(response)
+    }
+
+    /// Our end of `channel_id`, once the handshake has completed.
+    pub fn channel(&self, channel_id: &str) -> Option<&IbcChannel> {
+        self.channels.get(channel_id)
+    }
+
+    /// Queues a packet as if the app had returned `IbcMsg::SendPacket`. Returns its sequence.
+    pub fn send_packet(&mut self, channel_id: &str, data: impl Into<Binary>, timeout: IbcTimeout) -> u64 {
+        let channel = self
+            .channels
+            .get(channel_id)
+            .unwrap_or_else(|| panic!("unknown channel {} on port {}", channel_id, self.port_id));
+        let sequence = self.next_sequence.entry(channel_id.into()).or_insert(1);
+        let packet = IbcPacket {
+            data: data.into(),
+            src: channel.endpoint.clone(),
+            dest: channel.counterparty_endpoint.clone(),
+            sequence: *sequence,
+            timeout,
+        };
+        *sequence += 1;
+        self.outbox.push_back(packet);
+        *sequence - 1
+    }
+
+    /// Packets sent but not relayed yet.
+    pub fn pending_packets(&self) -> impl Iterator<Item = &IbcPacket> {
+        self.outbox.iter()
+    }
+
+    /// Packets received without acknowledgement, still waiting for `WriteAcknowledgement`.
+    pub fn unacknowledged_packets(&self) -> impl Iterator<Item = &IbcPacket> {
+        self.awaiting_ack.values()
+    }
+
+    /// Produces `blocks` blocks, moving the clock forward by `seconds`.
+    pub fn advance(&mut self, blocks: u64, seconds: u64) {
+        self.height.height += blocks;
+        self.time = self.time.plus_seconds(seconds).expect("simulated time overflow");
+    }
+
+    fn dispatch<T>(&mut self, messages: &[SubMsg<T>]) {
+        for sub_msg in messages {
+            match &sub_msg.msg {
+                CosmosMsg::Ibc(IbcMsg::SendPacket {
+                    channel_id,
+                    data,
+                    timeout,
+                }) => {
+                    self.send_packet(channel_id, data.clone(), timeout.clone());
+                }
+                CosmosMsg::Ibc(IbcMsg::Transfer {
+                    channel_id,
+                    to_address,
+                    amount,
+                    timeout,
+                }) => {
+                    // relayed over the app's own channel as an ICS-20 packet
+                    let packet = Ics20Packet::new(
+                        amount.amount,
+                        amount.denom.as_str(),
+                        self.addr().into_string(),
+                        to_address.as_str(),
+                        None,
+                    );
+                    self.send_packet(channel_id, packet.to_json(), timeout.clone());
+                }
+                CosmosMsg::Ibc(IbcMsg::WriteAcknowledgement {
+                    channel_id,
+                    packet_sequence,
+                    ack,
+                }) => {
+                    let packet = self
+                        .awaiting_ack
+                        .remove(&(channel_id.clone(), *packet_sequence))
+                        .unwrap_or_else(|| {
+                            panic!("packet {} on {} awaits no ack", packet_sequence, channel_id)
+                        });
+                    self.ack_outbox.push_back((packet, ack.data.clone()));
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Points the ink! environment at the contract deployed at `account`: storage access goes
+/// to its storage and `block_timestamp()` returns the time of its chain.
+fn enter(account: AccountId, time: IbcTimestamp) {
+    ink::env::test::set_callee::<DefaultEnvironment>(account);
+    ink::env::test::set_contract::<DefaultEnvironment>(account);
+    ink::env::test::set_block_timestamp::<DefaultEnvironment>(time.millis());
+}
+
+/// What happened to a relayed packet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PacketOutcome {
+    /// Received by the counterparty, whose acknowledgement was passed to `ibc_packet_ack`.
+    Acknowledged {
+        packet: IbcPacket,
+        acknowledgement: Binary,
+    },
+    /// Expired before delivery; the sender got `ibc_packet_timeout`.
+    TimedOut { packet: IbcPacket },
+    /// Received by the counterparty, which acknowledges it later.
+    Received { packet: IbcPacket },
+}
+
+/// Two chains, `a` and `b`, and a relayer between them.
+pub struct IbcSimulator<A, B> {
+    pub a: Chain<A>,
+    pub b: Chain<B>,
+    pub relayer: Addr,
+    connection_id: String,
+    next_channel: u64,
+}
+
+impl<A: IbcApp, B: IbcApp> IbcSimulator<A, B> {
+    pub fn new(a: Chain<A>, b: Chain<B>) -> Self {
+        IbcSimulator {
+            a,
+            b,
+            relayer: Addr::unchecked("relayer"),
+            connection_id: "connection-0".into(),
+            next_channel: 0,
+        }
+    }
+
+    /// Runs ChanOpenInit on `a`, ChanOpenTry on `b`, then ChanOpenAck and ChanOpenConfirm.
+    /// Returns the channel ids on `a` and `b`.
+    pub fn open_channel(&mut self, order: IbcOrder, version: &str) -> Result<(String, String), Error> {
+        // both chains allocate from the same counter, so ids never collide in assertions
+        let channel_a = format!("channel-{}", self.next_channel);
+        let channel_b = format!("channel-{}", self.next_channel + 1);
+        self.next_channel += 2;
+        let endpoint_a = IbcEndpoint {
+            port_id: self.a.port_id.clone(),
+            channel_id: channel_a.clone(),
+        };
+        let endpoint_b = IbcEndpoint {
+            port_id: self.b.port_id.clone(),
+            channel_id: channel_b.clone(),
+        };
+        let channel = |endpoint: &IbcEndpoint, counterparty: &IbcEndpoint, version: &str| IbcChannel {
+            endpoint: endpoint.clone(),
+            counterparty_endpoint: counterparty.clone(),
+            order: order.clone(),
+            version: version.into(),
+            connection_id: self.connection_id.clone(),
+        };
+
+        let init = channel(&endpoint_a, &endpoint_b, version);
+        let open_init = IbcChannelOpenMsg::OpenInit { channel: init };
+        let version_a = negotiated_version(self.a.call(|app| app.ibc_channel_open(open_init))?, version);
+
+        let try_channel = channel(&endpoint_b, &endpoint_a, &version_a);
+        let open_try = IbcChannelOpenMsg::OpenTry {
+            channel: try_channel,
+            counterparty_version: version_a.clone(),
+        };
+        let version_b = negotiated_version(self.b.call(|app| app.ibc_channel_open(open_try))?, &version_a);
+
+        let ack_channel = channel(&endpoint_a, &endpoint_b, &version_b);
+        let response = self.a.call(|app| {
+            app.ibc_channel_connect(IbcChannelConnectMsg::OpenAck {
+                channel: ack_channel.clone(),
+                counterparty_version: version_b.clone(),
+            })
+        })?;
+        self.a.channels.insert(channel_a.clone(), ack_channel);
+        self.a.dispatch(&response.messages);
+
+        let confirm_channel = channel(&endpoint_b, &endpoint_a, &version_b);
+        let response = self.b.call(|app| {
+            app.ibc_channel_connect(IbcChannelConnectMsg::OpenConfirm {
+                channel: confirm_channel.clone(),
+            })
+        })?;
+        self.b.channels.insert(channel_b.clone(), confirm_channel);
+        self.b.dispatch(&response.messages);
+
+        Ok((channel_a, channel_b))
+    }
+
+    /// Closes the channel `channel_a` of `a` (ChanCloseInit) and its counterpart on `b`.
+    pub fn close_channel(&mut self, channel_a: &str) -> Result<(), Error> {
+        let channel = self.a.channels.remove(channel_a).expect("unknown channel");
+        let counterparty = self
+            .b
+            .channels
+            .remove(&channel.counterparty_endpoint.channel_id)
+            .expect("unknown counterparty channel");
+        let response = self.a.call(|app| app.ibc_channel_close(IbcChannelCloseMsg::CloseInit { channel }))?;
+        self.a.dispatch(&response.messages);
+        let response = self.b.call(|app| {
+            app.ibc_channel_close(IbcChannelCloseMsg::CloseConfirm {
+                channel: counterparty,
+            })
+        })?;
+        self.b.dispatch(&response.messages);
+        Ok(())
+    }
+
+    /// Moves both chains forward.
+    pub fn advance(&mut self, blocks: u64, seconds: u64) {
+        self.a.advance(blocks, seconds);
+        self.b.advance(blocks, seconds);
+    }
+
+    /// Relays until neither chain has packets or written acks left, including packets sent
+    /// by the callbacks of relayed packets. Outcomes are returned in delivery order.
+    ///
+    /// An error returned by `ibc_packet_receive` aborts relaying, as it would abort the
+    /// relayer's transaction; the packet is dropped.
+    pub fn relay(&mut self) -> Result<Vec<PacketOutcome>, Error> {
+        let mut outcomes = Vec::new();
+        loop {
+            if let Some(packet) = self.a.outbox.pop_front() {
+                outcomes.push(deliver(&mut self.a, &mut self.b, packet, &self.relayer)?);
+            } else if let Some(packet) = self.b.outbox.pop_front() {
+                outcomes.push(deliver(&mut self.b, &mut self.a, packet, &self.relayer)?);
+            } else if let Some((packet, ack)) = self.b.ack_outbox.pop_front() {
+                outcomes.push(acknowledge(&mut self.a, packet, ack, &self.relayer)?);
+            } else if let Some((packet, ack)) = self.a.ack_outbox.pop_front() {
+                outcomes.push(acknowledge(&mut self.b, packet, ack, &self.relayer)?);
+            } else {
+                return Ok(outcomes);
+            }
+        }
+    }
+}
+
+fn deliver<S: IbcApp, D: IbcApp>(
+    src: &mut Chain<S>,
+    dest: &mut Chain<D>,
+    packet: IbcPacket,
+    relayer: &Addr,
+) -> Result<PacketOutcome, Error> {
+    if packet.timeout.is_expired(&dest.height, dest.time) {
+        let msg = timeout_msg(packet.clone(), relayer);
+        let response = src.call(|app| app.ibc_packet_timeout(msg))?;
+        src.dispatch(&response.messages);
+        return Ok(PacketOutcome::TimedOut { packet });
+    }
+
+    let msg = receive_msg(packet.clone(), relayer);
+    let response = dest.call(|app| app.ibc_packet_receive(msg))?;
+    if response.is_async() {
+        // registered before dispatching, the app may acknowledge right away
+        let key = (packet.dest.channel_id.clone(), packet.sequence);
+        dest.awaiting_ack.insert(key, packet.clone());
+        dest.dispatch(&response.messages);
+        return Ok(PacketOutcome::Received { packet });
+    }
+    dest.dispatch(&response.messages);
+    acknowledge(src, packet, response.acknowledgement, relayer)
+}
+
+fn acknowledge<S: IbcApp>(
+    src: &mut Chain<S>,
+    packet: IbcPacket,
+    acknowledgement: Binary,
+    relayer: &Addr,
+) -> Result<PacketOutcome, Error> {
+    let msg = ack_msg(IbcAcknowledgement::new(acknowledgement.clone()), packet.clone(), relayer);
+    let response = src.call(|app| app.ibc_packet_ack(msg))?;
+    src.dispatch(&response.messages);
+    Ok(PacketOutcome::Acknowledged {
+        packet,
+        acknowledgement,
+    })
+}
+
+#[cfg(feature = "ibc3")]
+fn negotiated_version(response: IbcChannelOpenResponse, proposed: &str) -> String {
+    match response {
+        Some(response) => response.version,
+        None => proposed.into(),
+    }
+}
+
+#[cfg(not(feature = "ibc3"))]
+fn negotiated_version(_response: IbcChannelOpenResponse, proposed: &str) -> String {
+    proposed.into()
+}
+
+#[cfg(feature = "ibc3")]
+fn receive_msg(packet: IbcPacket, relayer: &Addr) -> IbcPacketReceiveMsg {
+    IbcPacketReceiveMsg::new(packet, relayer.clone())
+}
+
+#[cfg(not(feature = "ibc3"))]
+fn receive_msg(packet: IbcPacket, _relayer: &Addr) -> IbcPacketReceiveMsg {
+    IbcPacketReceiveMsg::new(packet)
+}
+
+#[cfg(feature = "ibc3")]
+fn ack_msg(ack: IbcAcknowledgement, packet: IbcPacket, relayer: &Addr) -> IbcPacketAckMsg {
+    IbcPacketAckMsg::new(ack, packet, relayer.clone())
+}
+
+#[cfg(not(feature = "ibc3"))]
+fn ack_msg(ack: IbcAcknowledgement, packet: IbcPacket, _relayer: &Addr) -> IbcPacketAckMsg {
+    IbcPacketAckMsg::new(ack, packet)
+}
+
+#[cfg(feature = "ibc3")]
+fn timeout_msg(packet: IbcPacket, relayer: &Addr) -> IbcPacketTimeoutMsg {
+    IbcPacketTimeoutMsg::new(packet, relayer.clone())
+}
+
+#[cfg(not(feature = "ibc3"))]
+fn timeout_msg(packet: IbcPacket, _relayer: &Addr) -> IbcPacketTimeoutMsg {
+    IbcPacketTimeoutMsg::new(packet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ink::storage::Mapping;
+
+    /// Counts packets in contract storage, so two instances only stay apart if the
+    /// simulator keeps their storage apart. Answers "ping" with a "pong" packet.
+    #[derive(Default)]
+    struct PingPong {
+        received: Mapping<String, u32>,
+        acks: Vec<StdAck>,
+        timeouts: Vec<u64>,
+    }
+
+    impl PingPong {
+        fn received(&self, channel_id: &str) -> u32 {
+            self.received.get(String::from(channel_id)).unwrap_or_default()
+        }
+    }
+
+    impl IbcApp for PingPong {
+        fn ibc_channel_open(&self, msg: IbcChannelOpenMsg) -> Result<IbcChannelOpenResponse, Error> {
+            HandshakePolicy::new("ping-1").open_response(&msg)
+        }
+
+        fn ibc_channel_connect(
+            &mut self,
+            msg: IbcChannelConnectMsg,
+        ) -> Result<IbcBasicResponse, Error> {
+            HandshakePolicy::new("ping-1").validate_connect(&msg)?;
+            Ok(IbcBasicResponse::new())
+        }
+
+        fn ibc_channel_close(&self, _msg: IbcChannelCloseMsg) -> Result<IbcBasicResponse, Error> {
+            Ok(IbcBasicResponse::new())
+        }
+
+        fn ibc_packet_receive(
+            &mut self,
+            msg: IbcPacketReceiveMsg,
+        ) -> Result<IbcReceiveResponse, Error> {
+            let channel_id = msg.packet.dest.channel_id.clone();
+            self.received
+                .insert(channel_id.clone(), &(self.received(&channel_id) + 1));
+            if msg.packet.data.as_slice() == b"hold" {
+                return Ok(IbcReceiveResponse::without_ack());
+            }
+            let response = IbcReceiveResponse::new().set_ack(StdAck::success(msg.packet.data.clone()));
+            if msg.packet.data.as_slice() != b"ping" {
+                return Ok(response);
+            }
+            let timeout = IbcTimeout::with_timestamp(
+                IbcTimestamp::from_millis(ink::env::block_timestamp::<DefaultEnvironment>())
+                    .and_then(|now| now.plus_seconds(60))
+                    .unwrap(),
+            );
+            Ok(response.add_message(IbcMsg::SendPacket {
+                channel_id,
+                data: b"pong".to_vec().into(),
+                timeout,
+            }))
+        }
+
+        fn ibc_packet_ack(&mut self, msg: IbcPacketAckMsg) -> Result<IbcBasicResponse, Error> {
+            self.acks.push(msg.acknowledgement.std_ack()?);
+            Ok(IbcBasicResponse::new())
+        }
+
+        fn ibc_packet_timeout(&mut self, msg: IbcPacketTimeoutMsg) -> Result<IbcBasicResponse, Error> {
+            self.timeouts.push(msg.packet.sequence);
+            Ok(IbcBasicResponse::new())
+        }
+    }
+
+    fn simulator() -> IbcSimulator<PingPong, PingPong> {
+        let accounts = ink::env::test::default_accounts::<DefaultEnvironment>();
+        let a = Chain::instantiate(accounts.alice, "ping-a", PingPong::default);
+        let b = Chain::instantiate(accounts.bob, "ping-b", PingPong::default);
+        IbcSimulator::new(a, b)
+    }
+
+    fn in_seconds<A>(chain: &Chain<A>, seconds: u64) -> IbcTimeout {
+        IbcTimeout::with_timestamp(chain.time.plus_seconds(seconds).unwrap())
+    }
+
+    #[ink::test]
+    fn handshake_opens_both_ends() {
+        let mut sim = simulator();
+        let (channel_a, channel_b) = sim.open_channel(IbcOrder::Unordered, "ping-1").unwrap();
+        let end_a = sim.a.channel(&channel_a).unwrap();
+        assert_eq!(end_a.counterparty_endpoint.port_id, "ping-b");
+        assert_eq!(end_a.counterparty_endpoint.channel_id, channel_b);
+        assert_eq!(end_a.version, "ping-1");
+        assert_eq!(sim.b.channel(&channel_b).unwrap().counterparty_endpoint.channel_id, channel_a);
+
+        assert_eq!(
+            sim.open_channel(IbcOrder::Unordered, "ping-2"),
+            Err(Error::InvalidIbcVersion {
+                version: "ping-2".into()
+            })
+        );
+
+        sim.close_channel(&channel_a).unwrap();
+        assert!(sim.a.channel(&channel_a).is_none());
+        assert!(sim.b.channel(&channel_b).is_none());
+    }
+
+    #[ink::test]
+    fn relays_packets_and_acks_until_quiet() {
+        let mut sim = simulator();
+        let (channel_a, channel_b) = sim.open_channel(IbcOrder::Unordered, "ping-1").unwrap();
+
+        let timeout = in_seconds(&sim.a, 60);
+        assert_eq!(sim.a.send_packet(&channel_a, b"ping".to_vec(), timeout.clone()), 1);
+        assert_eq!(sim.a.send_packet(&channel_a, b"hello".to_vec(), timeout), 2);
+        let outcomes = sim.relay().unwrap();
+
+        // ping, hello, then the pong b sent while receiving the ping
+        assert_eq!(outcomes.len(), 3);
+        match &outcomes[2] {
+            PacketOutcome::Acknowledged {
+                packet,
+                acknowledgement,
+            } => {
+                assert_eq!(packet.data.as_slice(), b"pong");
+                assert_eq!(packet.src.channel_id, channel_b);
+                assert_eq!(packet.sequence, 1);
+                assert_eq!(acknowledgement, &Binary::from(StdAck::success(b"pong".to_vec())));
+            }
+            outcome => panic!("unexpected {:?}", outcome),
+        }
+        assert_eq!(
+            sim.a.app.acks,
+            vec![StdAck::success(b"ping".to_vec()), StdAck::success(b"hello".to_vec())]
+        );
+        assert_eq!(sim.b.app.acks, vec![StdAck::success(b"pong".to_vec())]);
+
+        // the apps share a storage layout but not their storage
+        let received_b = sim.b.call(|app| app.received(&channel_b));
+        let received_a = sim.a.call(|app| app.received(&channel_a));
+        assert_eq!((received_a, received_b), (1, 2));
+        assert!(sim.relay().unwrap().is_empty());
+    }
+
+    #[ink::test]
+    fn expired_packets_time_out_on_the_sender() {
+        let mut sim = simulator();
+        let (channel_a, _) = sim.open_channel(IbcOrder::Unordered, "ping-1").unwrap();
+
+        let by_time = in_seconds(&sim.a, 10);
+        sim.a.send_packet(&channel_a, b"late".to_vec(), by_time);
+        let by_height = IbcTimeout::with_block(IbcTimeoutBlock::new(1, 5));
+        sim.a.send_packet(&channel_a, b"later".to_vec(), by_height);
+        let in_time = in_seconds(&sim.a, 3600);
+        sim.a.send_packet(&channel_a, b"fine".to_vec(), in_time);
+        assert_eq!(sim.a.pending_packets().count(), 3);
+
+        sim.advance(4, 10);
+        let outcomes = sim.relay().unwrap();
+        assert!(matches!(outcomes[0], PacketOutcome::TimedOut { .. }));
+        assert!(matches!(outcomes[1], PacketOutcome::TimedOut { .. }));
+        assert!(matches!(outcomes[2], PacketOutcome::Acknowledged { .. }));
+        assert_eq!(sim.a.app.timeouts, vec![1, 2]);
+        assert_eq!(sim.a.app.acks.len(), 1);
+    }
+
+    #[ink::test]
+    fn relays_acks_written_later() {
+        let mut sim = simulator();
+        let (channel_a, channel_b) = sim.open_channel(IbcOrder::Unordered, "ping-1").unwrap();
+
+        let timeout = in_seconds(&sim.a, 60);
+        let sequence = sim.a.send_packet(&channel_a, b"hold".to_vec(), timeout);
+        let outcomes = sim.relay().unwrap();
+        assert!(matches!(outcomes[0], PacketOutcome::Received { .. }));
+        assert_eq!(sim.b.unacknowledged_packets().count(), 1);
+        assert!(sim.a.app.acks.is_empty());
+
+        let write_ack = IbcMsg::WriteAcknowledgement {
+            channel_id: channel_b,
+            packet_sequence: sequence,
+            ack: IbcAcknowledgement::new(StdAck::success(b"done".to_vec())),
+        };
+        sim.b
+            .execute(|_| Ok::<_, Error>(Response::<Empty>::new().add_message(write_ack)))
+            .unwrap();
+        let outcomes = sim.relay().unwrap();
+        assert!(matches!(outcomes[0], PacketOutcome::Acknowledged { .. }));
+        assert_eq!(sim.a.app.acks, vec![StdAck::success(b"done".to_vec())]);
+        assert_eq!(sim.b.unacknowledged_packets().count(), 0);
+    }
+
+    #[ink::test]
+    fn relays_ics20_transfers() {
+        let mut sim = simulator();
+        let (channel_a, _) = sim.open_channel(IbcOrder::Unordered, "ping-1").unwrap();
+
+        let transfer = IbcMsg::Transfer {
+            channel_id: channel_a,
+            to_address: "bob".into(),
+            amount: Coin {
+                denom: "ERT".into(),
+                amount: 5,
+            },
+            timeout: in_seconds(&sim.a, 60),
+        };
+        sim.a
+            .execute(|_| Ok::<_, Error>(Response::<Empty>::new().add_message(transfer)))
+            .unwrap();
+        let outcomes = sim.relay().unwrap();
+        let packet = match &outcomes[0] {
+            PacketOutcome::Acknowledged { packet, .. } => packet,
+            outcome => panic!("unexpected {:?}", outcome),
+        };
+        let data = Ics20Packet::from_json(&packet.data).unwrap();
+        assert_eq!(data, Ics20Packet::new(5, "ERT", sim.a.addr().into_string(), "bob", None));
+        assert!(data.sender.starts_with("0x") && data.sender.len() == 66);
+    }
+}